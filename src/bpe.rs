@@ -10,11 +10,20 @@
 //! https://towardsdatascience.com/byte-pair-encoding-subword-based-tokenization-algorithm-77828a70bee0
 
 use csv::{Reader, Writer};
-use radsort::sort_by_key;
-use std::{collections::HashMap, error::Error, ffi::OsStr};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, VecDeque},
+    error::Error,
+    ffi::OsStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
 
 pub use crate::debug_tools::*;
 pub use crate::tokenize::*;
+pub use crate::trie::*;
 
 #[derive(Debug, Clone)]
 struct WordCount {
@@ -30,26 +39,51 @@ pub struct Frequency {
 
 // Init the vocab as all the lowercase letters and punctuation.
 // Build the corpus as a list of letters for each word, and their counts.
-fn init_vocab_corpus(token_lines: Vec<Vec<String>>) -> (Vec<Frequency>, Vec<WordCount>) {
+// Lossless tokens keep their case, and need no </w> since SPACE_MARKER starts each word.
+// Words are counted first, borrowing from token_lines, so each distinct word is split into letters only once.
+fn init_vocab_corpus(
+    token_lines: Vec<Vec<String>>,
+    lossless: bool,
+) -> (Vec<Frequency>, Vec<WordCount>) {
+    let mut word_counts: HashMap<Cow<str>, u32> = HashMap::new();
+    for token in token_lines.iter().flatten() {
+        let word = if lossless || is_case_marker(token) {
+            Cow::Borrowed(token.as_str())
+        } else {
+            lowercase(token)
+        };
+        *word_counts.entry(word).or_insert(0) += 1;
+    }
+
+    let mut letter_freq: HashMap<char, u32> = HashMap::new();
     let mut word_freq: HashMap<String, u32> = HashMap::new();
     let mut corpus: HashMap<Vec<String>, u32> = HashMap::new();
-    for line in token_lines {
-        for token in line {
-            let mut word: Vec<String> = Vec::new();
-            for c in token.to_lowercase().chars() {
-                word.push(c.to_string());
+    for (token, count) in word_counts {
+        // Case markers stay whole, so they never merge with letters.
+        if is_case_marker(&token) {
+            *word_freq.entry(token.to_string()).or_insert(0) += count;
+            corpus.insert(vec![token.into_owned()], count);
+            continue;
+        }
+        let mut word: Vec<String> = Vec::new();
+        for c in token.chars() {
+            word.push(c.to_string());
 
-                // Build the frequency table with vocab of letters, and letter counts.
-                *word_freq.entry(c.to_string()).or_insert(0) += 1;
-            }
+            // Build the frequency table with vocab of letters, and letter counts.
+            *letter_freq.entry(c).or_insert(0) += count;
+        }
+        if !lossless {
             word.push("</w>".to_string());
-
-            // Create dict with counts of words using 'entry'
-            // A dict is a more performant way to build this than vec of structs,
-            // since we have to check for belonging on every word of the corpus.
-            // Source https://stackoverflow.com/questions/64178272/what-is-the-idiomatic-rust-way-to-build-a-hashmap-of-character-counts
-            *corpus.entry(word).or_insert(0) += 1;
         }
+
+        // Create dict with counts of words using 'entry'
+        // A dict is a more performant way to build this than vec of structs,
+        // since we have to check for belonging on every word of the corpus.
+        // Source https://stackoverflow.com/questions/64178272/what-is-the-idiomatic-rust-way-to-build-a-hashmap-of-character-counts
+        *corpus.entry(word).or_insert(0) += count;
+    }
+    for (c, count) in letter_freq {
+        *word_freq.entry(c.to_string()).or_insert(0) += count;
     }
     // Convert both tables after creation, since we don't need fast lookup after this.
     // source:
@@ -166,15 +200,153 @@ fn merge_bigrams(mut corpus: Vec<WordCount>, max_bigram: String) -> Vec<WordCoun
     corpus
 }
 
+/// How a corpus is tokenized before BPE training and encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BpeMode {
+    /// Lowercased words from tokenize_line_alphas_lowercase().
+    Lowercase,
+    /// Lowercased words with case markers, from case_mark_tokens().
+    CaseMarkers,
+    /// Every character kept, from tokenize_line_lossless().
+    Lossless,
+}
+
+impl BpeMode {
+    /// The Tokenizer that prepares text for this mode.
+    pub fn tokenizer(&self) -> Box<dyn Tokenizer> {
+        match self {
+            BpeMode::Lowercase => Box::new(AlphasLowercaseTokenizer),
+            BpeMode::CaseMarkers => Box::new(CaseMarkedTokenizer),
+            BpeMode::Lossless => Box::new(LosslessTokenizer),
+        }
+    }
+}
+
+/// Accepts a path to a CSV file and a BpeMode.
+/// Returns the tokenized second column of each row as prepared for that mode, or any resultant errors.
+pub fn parse_csv_for_bpe(fpath: &OsStr, mode: BpeMode) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    parse_csv_to_tokens_with(fpath, mode.tokenizer().as_ref())
+}
+
+/// Lowercase word tokens with case markers, using tokenize_line_alphas() and case_mark_tokens().
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaseMarkedTokenizer;
+
+impl Tokenizer for CaseMarkedTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        case_mark_tokens(&tokenize_line_alphas(line))
+    }
+}
+
+/// Marker inserted before a word whose first letter is upper case.
+pub const CAP_MARKER: &str = "<cap>";
+/// Marker inserted before a word written entirely in upper case.
+pub const ALLCAPS_MARKER: &str = "<allcaps>";
+
+/// Returns true if token is one of the case marker tokens.
+pub fn is_case_marker(token: &str) -> bool {
+    token == CAP_MARKER || token == ALLCAPS_MARKER
+}
+
+/// Lowercases each word, inserting a case marker before it so the casing can be restored.
+/// Words like "Is" get CAP_MARKER, and words like "IS" with two or more letters get ALLCAPS_MARKER.
+/// Words with mixed casing, like "iPhone", are lowercased without a marker, so their casing is lost.
+pub fn case_mark_tokens(tokens: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for token in tokens {
+        let letters: Vec<char> = token.chars().filter(|c| c.is_alphabetic()).collect();
+        let upper = letters.iter().filter(|c| c.is_uppercase()).count();
+        if upper == 0 {
+            out.push(token.clone());
+        } else if upper == letters.len() && letters.len() > 1 {
+            out.push(ALLCAPS_MARKER.to_string());
+            out.push(token.to_lowercase());
+        } else if upper == 1 && letters[0].is_uppercase() {
+            out.push(CAP_MARKER.to_string());
+            out.push(token.to_lowercase());
+        } else {
+            out.push(token.to_lowercase());
+        }
+    }
+    out
+}
+
+/// Reverses case_mark_tokens(), applying each case marker to the word after it.
+pub fn case_restore_tokens(tokens: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    let mut marker: Option<&str> = None;
+    for token in tokens {
+        if is_case_marker(token) {
+            marker = Some(token);
+            continue;
+        }
+        match marker.take() {
+            Some(ALLCAPS_MARKER) => out.push(token.to_uppercase()),
+            Some(_) => {
+                let mut chars = token.chars();
+                let first = chars.next().map(|c| c.to_uppercase().to_string());
+                out.push(first.unwrap_or_default() + chars.as_str());
+            }
+            None => out.push(token.clone()),
+        }
+    }
+    out
+}
+
+/// Applies case_mark_tokens() to every line, for training or encoding with case markers.
+pub fn case_mark_lines(token_lines: Vec<Vec<String>>) -> Vec<Vec<String>> {
+    token_lines
+        .iter()
+        .map(|line| case_mark_tokens(line))
+        .collect()
+}
+
+/// A pair of tokens merged during BPE training.
+/// The rank of a merge is its position in the list returned by bpe_training_with_merges().
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Merge {
+    pub left: String,
+    pub right: String,
+}
+
+impl Merge {
+    /// The token produced by this merge.
+    pub fn merged(&self) -> String {
+        format!("{}{}", self.left, self.right)
+    }
+}
+
 // Use byte-pair encoding to build a vocabulary of size n,
 // according to statistical frequency of bi-gram.
-pub fn bpe_training(token_lines: Vec<Vec<String>>, mut n: u8) -> Vec<String> {
+pub fn bpe_training(token_lines: Vec<Vec<String>>, n: u8) -> Vec<String> {
+    bpe_training_with_merges(token_lines, n).0
+}
+
+/// Same as bpe_training(), but also returns every merge performed, in the order they fired.
+pub fn bpe_training_with_merges(token_lines: Vec<Vec<String>>, n: u8) -> (Vec<String>, Vec<Merge>) {
+    let (frequency_table, corpus) = init_vocab_corpus(token_lines, false);
+    bpe_merge_loop(frequency_table, corpus, n)
+}
+
+/// Same as bpe_training_with_merges(), for lines from tokenize_line_lossless().
+/// Tokens are not lowercased and get no </w>, so the vocab can rebuild the text exactly.
+pub fn bpe_training_lossless(token_lines: Vec<Vec<String>>, n: u8) -> (Vec<String>, Vec<Merge>) {
+    let (frequency_table, corpus) = init_vocab_corpus(token_lines, true);
+    bpe_merge_loop(frequency_table, corpus, n)
+}
+
+// Merge the most frequent bigram until the vocab reaches size n.
+fn bpe_merge_loop(
+    mut frequency_table: Vec<Frequency>,
+    mut corpus: Vec<WordCount>,
+    mut n: u8,
+) -> (Vec<String>, Vec<Merge>) {
     // A common value of n: 50,000
     let min_corpus_size = 52;
     if n < min_corpus_size {
         n = min_corpus_size;
     }
-    let (mut frequency_table, mut corpus) = init_vocab_corpus(token_lines);
+    let mut merges: Vec<Merge> = Vec::new();
 
     // Loop over corpus, expanding vocab with next most likely bigram,
     // until desired vocab size reached.
@@ -183,10 +355,18 @@ pub fn bpe_training(token_lines: Vec<Vec<String>>, mut n: u8) -> Vec<String> {
         let (max_bigram, bigram_count) = get_max_freq_bigram(&corpus);
 
         // Exit early if no more mergeable bigrams.
-        if [max_bigram[0].clone(), max_bigram[1].clone()].join("") == "" {
+        if [max_bigram[0].clone(), max_bigram[1].clone()]
+            .join("")
+            .is_empty()
+        {
             break;
         }
 
+        merges.push(Merge {
+            left: max_bigram[0].clone(),
+            right: max_bigram[1].clone(),
+        });
+
         // Update the frequency table
         frequency_table = update_frequency_table(frequency_table, &max_bigram, &bigram_count);
 
@@ -200,71 +380,460 @@ pub fn bpe_training(token_lines: Vec<Vec<String>>, mut n: u8) -> Vec<String> {
     for entry in &frequency_table {
         vocab.push(entry.token.clone());
     }
-    vocab
+    (vocab, merges)
 }
 
+/// Tokenizes each sequence against the vocab, with a fresh word cache for this call.
+/// Returns the subword tokens of each sequence.  See bpe_encoding_cached().
 pub fn bpe_encoding(
     text_lines: Vec<Vec<String>>,
-    mut vocab_lines: Vec<Vec<String>>,
+    vocab_lines: Vec<Vec<String>>,
 ) -> Vec<Vec<String>> {
-    //iterate over vocab
-    // order longest token of vocab to shortest
+    let cache = BpeCache::new(DEFAULT_CACHE_CAPACITY);
+    bpe_encoding_cached(text_lines, vocab_lines, &cache)
+}
+
+/// Tokenizes each sequence against the vocab, reusing segmentations stored in cache.
+/// Every word is split greedily, taking the longest vocab token that matches at each position.
+/// Characters matching no vocab token are replaced by UNKNOWN_TOKEN.
+/// The cache is keyed by word only, so it must not be shared between different vocabs.
+pub fn bpe_encoding_cached(
+    text_lines: Vec<Vec<String>>,
+    vocab_lines: Vec<Vec<String>>,
+    cache: &BpeCache,
+) -> Vec<Vec<String>> {
+    let vocab_trie = VocabTrie::from_tokens(format_vocab(&vocab_lines));
+
+    let mut encoded_seqs: Vec<Vec<String>> = Vec::new();
+    for sequence in &text_lines {
+        encoded_seqs.push(encode_tokens(sequence, &vocab_trie, cache, false));
+    }
+    /*
+    Future:
+    retrain bpe with the unknown words,
+    add results to the vocab,
+    and tokenize the unknown words.
+    */
+
+    encoded_seqs
+}
+
+/// Tokenizes each sequence of lossless tokens against a vocab from bpe_training_lossless().
+/// Characters matching no vocab token are kept as they are rather than replaced,
+/// so joining the output with detokenize_lossless() returns the original text.
+pub fn bpe_encoding_lossless(
+    text_lines: Vec<Vec<String>>,
+    vocab: Vec<String>,
+    cache: &BpeCache,
+) -> Vec<Vec<String>> {
+    let vocab_trie = VocabTrie::from_tokens(&vocab);
+
+    let mut encoded_seqs: Vec<Vec<String>> = Vec::new();
+    for sequence in &text_lines {
+        encoded_seqs.push(encode_tokens(sequence, &vocab_trie, cache, true));
+    }
+    encoded_seqs
+}
+
+// Converts vocab lines from parse_txt_to_tokens() back into tokens, restoring the </w> indicator.
+fn format_vocab(vocab_lines: &[Vec<String>]) -> Vec<String> {
     let mut formatted_vocab: Vec<String> = Vec::new();
-    // Sort by token length, not including the end of word indicator.
-    // source: https://docs.rs/radsort/latest/radsort/fn.sort_by_key.html
-    sort_by_key(&mut vocab_lines, |s| s[0].len());
-    for entry in &mut vocab_lines {
+    for entry in vocab_lines {
         if entry.len() == 2 {
             formatted_vocab.push(format!("{}{}", &entry[0], "</w>"));
         } else {
             formatted_vocab.push(entry[0].clone());
         }
     }
+    formatted_vocab
+}
 
-    let mut formatted_seqs: Vec<Vec<String>> = Vec::new();
-    // process input text and add </w> to the end of each word
-    for sequence in &text_lines {
-        let mut tmp_line: Vec<String> = Vec::new();
-        for token in sequence {
-            tmp_line.push(format!("{}{}", token, "</w>"));
+// Encode one sequence of tokens, segmenting each distinct token once through the cache.
+// Lossless tokens are matched as they are, and unmatched characters are kept.
+fn encode_tokens<I>(
+    sequence: I,
+    vocab_trie: &VocabTrie,
+    cache: &BpeCache,
+    lossless: bool,
+) -> Vec<String>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut tmp_line: Vec<String> = Vec::new();
+    for token in sequence {
+        let token = token.as_ref();
+        if !lossless && is_case_marker(token) {
+            tmp_line.push(token.to_string());
+            continue;
         }
-        formatted_seqs.push(tmp_line);
+        let segments = cache.get_or_insert_with(token, || {
+            if lossless {
+                segment_lossless(token, vocab_trie)
+            } else {
+                // Add </w> to the end of each word before matching.
+                segment_word(&format!("{}{}", token, "</w>"), vocab_trie)
+            }
+        });
+        tmp_line.extend(segments);
     }
-    /*
-    Not yet implemented:
-    for each token of the input sequence,
-    at every position of the token,
-    compare to every token of the vocab.
-    Walk the vocab in reverse order.
+    tmp_line
+}
 
-    If a match is found, replace.
+// Split one lossless token into vocab tokens, keeping unmatched characters as their own token.
+pub(crate) fn segment_lossless(token: &str, vocab_trie: &VocabTrie) -> Vec<String> {
+    let mut segments: Vec<String> = Vec::new();
+    let mut rest = token;
+    while let Some(c) = rest.chars().next() {
+        let end = vocab_trie
+            .longest_prefix(rest)
+            .map_or(c.len_utf8(), |t| t.len());
+        segments.push(rest[..end].to_string());
+        rest = &rest[end..];
+    }
+    segments
+}
 
-    Any substrings left in the input are replaced by </unknown> for now
+// Split one </w> terminated word into vocab tokens,
+// taking the longest vocab token at every position of the word.
+pub(crate) fn segment_word(word: &str, vocab_trie: &VocabTrie) -> Vec<String> {
+    let mut segments: Vec<String> = Vec::new();
+    let mut rest = word;
+    while !rest.is_empty() {
+        if let Some(token) = vocab_trie.longest_prefix(rest) {
+            segments.push(token.to_string());
+            rest = &rest[token.len()..];
+        } else if rest == "</w>" {
+            // No end of word token matched, the previous segment already ends the word.
+            break;
+        } else {
+            segments.push(UNKNOWN_TOKEN.to_string());
+            let skip = rest.chars().next().unwrap().len_utf8();
+            rest = &rest[skip..];
+        }
+    }
+    segments
+}
 
-    Future:
-    retrain bpe with these new words,
-    add results to the vocab,
-    and tokenize the unknown words.
-    */
+/// Joins the subword tokens of one encoded sequence back into words.
+/// Words end at each token ending in </w> or before a case marker, and case markers are applied to restore casing.
+/// A word whose end was not in the vocab has no </w> token, so it is joined to the word after it.
+pub fn bpe_decoding(tokens: &[String]) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    for token in tokens {
+        if is_case_marker(token) {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            words.push(token.clone());
+        } else if let Some(end) = token.strip_suffix("</w>") {
+            word.push_str(end);
+            words.push(std::mem::take(&mut word));
+        } else {
+            word.push_str(token);
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    case_restore_tokens(&words)
+}
+
+/// Number of distinct words bpe_encoding() remembers per call.
+pub const DEFAULT_CACHE_CAPACITY: usize = 100_000;
 
-    formatted_seqs
+// Cached segmentations, and the order their words were inserted for eviction.
+#[derive(Debug, Default)]
+struct CacheEntries {
+    words: HashMap<String, Vec<String>>,
+    order: VecDeque<String>,
+}
+
+/// Bounded word to segmentation cache for BPE encoding.
+/// Safe to share between threads; once full, the oldest word is evicted first.
+#[derive(Debug)]
+pub struct BpeCache {
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl BpeCache {
+    /// Creates an empty cache holding at most capacity words.
+    pub fn new(capacity: usize) -> BpeCache {
+        BpeCache {
+            capacity,
+            entries: Mutex::new(CacheEntries::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the cached segmentation of word, or computes it with segment and stores it.
+    pub fn get_or_insert_with<F>(&self, word: &str, segment: F) -> Vec<String>
+    where
+        F: FnOnce() -> Vec<String>,
+    {
+        if let Some(found) = self.entries.lock().unwrap().words.get(word) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return found.clone();
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // Segment without holding the lock, so other threads are not blocked.
+        let segments = segment();
+        if self.capacity > 0 {
+            let mut entries = self.entries.lock().unwrap();
+            if !entries.words.contains_key(word) {
+                if entries.words.len() >= self.capacity {
+                    if let Some(oldest) = entries.order.pop_front() {
+                        entries.words.remove(&oldest);
+                    }
+                }
+                entries.words.insert(word.to_string(), segments.clone());
+                entries.order.push_back(word.to_string());
+            }
+        }
+        segments
+    }
+
+    /// Number of lookups answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Number of lookups that had to segment the word.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Share of lookups answered from the cache, between 0 and 1.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits() + self.misses();
+        if total == 0 {
+            0.0
+        } else {
+            self.hits() as f64 / total as f64
+        }
+    }
+
+    /// Number of words currently cached.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().words.len()
+    }
+
+    /// Returns true if no words are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// BPE as a Tokenizer: text is split by the Tokenizer of its BpeMode, then each token is encoded against the vocab.
+/// The Tokenizer of the mode and the vocab trie are built once, and segmentations are cached across calls.
+pub struct BpeTokenizer {
+    mode: BpeMode,
+    tokenizer: Box<dyn Tokenizer>,
+    vocab_trie: VocabTrie,
+    cache: BpeCache,
+}
+
+impl BpeTokenizer {
+    /// Creates a BpeTokenizer from vocab tokens, as returned by training.
+    pub fn new(vocab: &[String], mode: BpeMode) -> BpeTokenizer {
+        BpeTokenizer {
+            mode,
+            tokenizer: mode.tokenizer(),
+            vocab_trie: VocabTrie::from_tokens(vocab),
+            cache: BpeCache::new(DEFAULT_CACHE_CAPACITY),
+        }
+    }
+
+    /// Creates a BpeTokenizer from a vocab file written by save_bpe_vocab().
+    pub fn from_vocab_file(fpath: &OsStr, mode: BpeMode) -> Result<BpeTokenizer, Box<dyn Error>> {
+        Ok(BpeTokenizer::new(&parse_txt_for_bpe(fpath, mode)?, mode))
+    }
+
+    /// The word cache shared by every call to tokenize().
+    pub fn cache(&self) -> &BpeCache {
+        &self.cache
+    }
+}
+
+impl Tokenizer for BpeTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        encode_tokens(
+            self.tokenizer.tokens(line),
+            &self.vocab_trie,
+            &self.cache,
+            self.mode == BpeMode::Lossless,
+        )
+    }
+}
+
+/// Token used to fill short sequences in a batch.  Always id 0.
+pub const PAD_TOKEN: &str = "<pad>";
+/// Token used for anything not found in the vocab.  Always id 1.
+pub const UNKNOWN_TOKEN: &str = "</unknown>";
+
+/// Selects which part of a sequence is cut when it is longer than the batch max length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Truncation {
+    /// Drop tokens from the start, keeping the end of the sequence.
+    Head,
+    /// Drop tokens from the end, keeping the start of the sequence.
+    Tail,
+    /// Drop tokens from the middle, keeping both ends of the sequence.
+    Middle,
+}
+
+/// A window over a sequence that did not fit in the batch.
+/// sequence is the index of the source sequence in the batch input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverflowWindow {
+    pub sequence: usize,
+    pub ids: Vec<u32>,
+    pub attention_mask: Vec<u8>,
+}
+
+/// Padded id matrix and attention masks produced by bpe_batch_encoding().
+/// Every row of ids and attention_mask is exactly max_len long.
+/// A mask value of 1 marks a real token, 0 marks padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchEncoding {
+    pub ids: Vec<Vec<u32>>,
+    pub attention_mask: Vec<Vec<u8>>,
+    pub overflow: Vec<OverflowWindow>,
+}
+
+/// Builds the token to id table for a vocab, in the order the vocab was loaded.
+/// Ids 0 and 1 are reserved for PAD_TOKEN and UNKNOWN_TOKEN.
+pub fn bpe_vocab_ids(vocab_lines: &[Vec<String>]) -> HashMap<String, u32> {
+    let mut ids: HashMap<String, u32> = HashMap::new();
+    ids.insert(PAD_TOKEN.to_string(), 0);
+    ids.insert(UNKNOWN_TOKEN.to_string(), 1);
+    for token in format_vocab(vocab_lines) {
+        let next_id = ids.len() as u32;
+        ids.entry(token).or_insert(next_id);
+    }
+    ids
+}
+
+// Returns the start and end of the tokens truncation drops from a sequence of len tokens,
+// an empty span if the sequence fits in max_len.
+fn dropped_span(len: usize, max_len: usize, truncation: Truncation) -> (usize, usize) {
+    if len <= max_len {
+        return (len, len);
+    }
+    match truncation {
+        Truncation::Head => (0, len - max_len),
+        Truncation::Tail => (max_len, len),
+        Truncation::Middle => (max_len - max_len / 2, len - max_len / 2),
+    }
+}
+
+// Pad a run of ids out to max_len, and build its attention mask.
+fn pad_window(window: &[u32], max_len: usize) -> (Vec<u32>, Vec<u8>) {
+    let mut ids = window.to_vec();
+    let mut mask = vec![1; window.len()];
+    ids.resize(max_len, 0);
+    mask.resize(max_len, 0);
+    (ids, mask)
+}
+
+/// Encodes many sequences with bpe_encoding() and converts them into a padded id matrix.
+/// Sequences longer than max_len are truncated as selected by truncation.
+/// So nothing is silently cut, the tokens dropped from each long sequence are also returned as overflow windows.
+/// Windows are max_len long, consecutive windows share stride tokens,
+/// and the first window starts stride tokens before the dropped tokens.
+pub fn bpe_batch_encoding(
+    text_lines: Vec<Vec<String>>,
+    vocab_lines: Vec<Vec<String>>,
+    max_len: usize,
+    truncation: Truncation,
+    stride: usize,
+) -> BatchEncoding {
+    let vocab_ids = bpe_vocab_ids(&vocab_lines);
+    let encoded = bpe_encoding(text_lines, vocab_lines);
+
+    let mut batch = BatchEncoding {
+        ids: Vec::new(),
+        attention_mask: Vec::new(),
+        overflow: Vec::new(),
+    };
+    // A stride as large as the window would never advance, so always move at least one token.
+    let step = max_len.saturating_sub(stride).max(1);
+
+    for (sequence, line) in encoded.iter().enumerate() {
+        let line_ids: Vec<u32> = line
+            .iter()
+            .map(|token| *vocab_ids.get(token).unwrap_or(&1))
+            .collect();
+
+        let (dropped_start, dropped_end) = dropped_span(line_ids.len(), max_len, truncation);
+        let mut kept = line_ids[..dropped_start].to_vec();
+        kept.extend_from_slice(&line_ids[dropped_end..]);
+        let (ids, mask) = pad_window(&kept, max_len);
+        batch.ids.push(ids);
+        batch.attention_mask.push(mask);
+
+        // Windows over the dropped tokens, the first starting stride tokens into the kept tokens before them.
+        if dropped_start < dropped_end && max_len > 0 {
+            let mut start = dropped_start.saturating_sub(stride);
+            loop {
+                let end = (start + max_len).min(line_ids.len());
+                let (ids, attention_mask) = pad_window(&line_ids[start..end], max_len);
+                batch.overflow.push(OverflowWindow {
+                    sequence,
+                    ids,
+                    attention_mask,
+                });
+                if end >= dropped_end {
+                    break;
+                }
+                start += step;
+            }
+        }
+    }
+    batch
 }
 
 /// Accepts a path to a CSV file.
 /// Returns a Vec of Vec of basic tokenized Strings for further processing by BPE, or any resultant errors.
 pub fn parse_csv_to_tokens(fpath: &OsStr) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    parse_csv_to_tokens_with(fpath, &AlphasLowercaseTokenizer)
+}
+
+/// Accepts a path to a CSV file and a Tokenizer.
+/// Returns a Vec of Vec of the second column of each row tokenized by the Tokenizer, or any resultant errors.
+pub fn parse_csv_to_tokens_with(
+    fpath: &OsStr,
+    tokenizer: &dyn Tokenizer,
+) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
     let mut out: Vec<Vec<String>> = Vec::new();
     let mut reader = Reader::from_path(fpath)?;
 
     for result in reader.byte_records() {
         let record = result?;
-        let tokens =
-            tokenize_line_alphas_lowercase(&String::from_utf8_lossy(record.get(1).unwrap()));
+        let tokens = tokenizer.tokenize(&String::from_utf8_lossy(record.get(1).unwrap()));
         out.push(tokens)
     }
     Ok(out)
 }
 
+/// Accepts a path to a CSV file.
+/// Returns a Vec of Vec of lowercased tokens with case markers, for BPE with casing kept, or any resultant errors.
+pub fn parse_csv_to_case_marked_tokens(fpath: &OsStr) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    parse_csv_to_tokens_with(fpath, &CaseMarkedTokenizer)
+}
+
+/// Accepts a path to a CSV file.
+/// Returns a Vec of Vec of lossless tokens from tokenize_line_lossless(), or any resultant errors.
+pub fn parse_csv_to_lossless_tokens(fpath: &OsStr) -> Result<Vec<Vec<String>>, Box<dyn Error>> {
+    parse_csv_to_tokens_with(fpath, &LosslessTokenizer)
+}
+
 /// Accepts a path to a CSV file.
 /// Returns a Vec of Sequences to be encoded by BPE, or any resultant errors.
 pub fn parse_csv_to_lines(fpath: &OsStr) -> Result<Vec<String>, Box<dyn Error>> {
@@ -287,8 +856,13 @@ pub fn parse_txt_to_tokens(fpath: &OsStr) -> Result<Vec<Vec<String>>, Box<dyn Er
 
     for result in reader.byte_records() {
         let record = result?;
-        let tokens =
-            tokenize_line_alphas_lowercase(&String::from_utf8_lossy(record.get(0).unwrap()));
+        let line = String::from_utf8_lossy(record.get(0).unwrap());
+        // Case markers would lose their brackets to the tokenizer.
+        let tokens = if is_case_marker(&line) {
+            vec![line.to_string()]
+        } else {
+            tokenize_line_alphas_lowercase(&line)
+        };
         out.push(tokens)
     }
     Ok(out)
@@ -320,6 +894,58 @@ pub fn save_bpe_vocab(fpath: &OsStr, to_save: &Vec<String>) -> Result<(), Box<dy
     Ok(())
 }
 
+/// Accepts a path to a vocab file written by save_bpe_vocab().
+/// Returns each vocab token exactly as saved, as needed by bpe_encoding_lossless(), or any resultant errors.
+pub fn parse_txt_to_vocab(fpath: &OsStr) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut out: Vec<String> = Vec::new();
+    let mut reader = Reader::from_path(fpath)?;
+
+    for result in reader.records() {
+        let record = result?;
+        out.push(record.get(0).unwrap_or_default().to_string())
+    }
+    Ok(out)
+}
+
+/// Accepts a path to a vocab file written by save_bpe_vocab() and the BpeMode it was trained with.
+/// Returns the vocab tokens as the encoder matches them, with </w> restored unless the mode is lossless,
+/// or any resultant errors.
+pub fn parse_txt_for_bpe(fpath: &OsStr, mode: BpeMode) -> Result<Vec<String>, Box<dyn Error>> {
+    if mode == BpeMode::Lossless {
+        parse_txt_to_vocab(fpath)
+    } else {
+        Ok(format_vocab(&parse_txt_to_tokens(fpath)?))
+    }
+}
+
+/// Takes a filepath as an &OsStr and a &[Merge] to save into a TXT, one merge per line in rank order.
+/// Returns an error if one occurs
+pub fn save_bpe_merges(fpath: &OsStr, to_save: &[Merge]) -> Result<(), Box<dyn Error>> {
+    let mut wtr = Writer::from_path(fpath)?;
+    wtr.write_record(["left", "right"])?;
+
+    for merge in to_save {
+        wtr.write_record([&merge.left, &merge.right])?;
+    }
+    Ok(())
+}
+
+/// Accepts a path to a merges file written by save_bpe_merges().
+/// Returns the merges in rank order, or any resultant errors.
+pub fn parse_txt_to_merges(fpath: &OsStr) -> Result<Vec<Merge>, Box<dyn Error>> {
+    let mut out: Vec<Merge> = Vec::new();
+    let mut reader = Reader::from_path(fpath)?;
+
+    for result in reader.records() {
+        let record = result?;
+        out.push(Merge {
+            left: record.get(0).unwrap_or_default().to_string(),
+            right: record.get(1).unwrap_or_default().to_string(),
+        })
+    }
+    Ok(out)
+}
+
 /// Takes a filepath as an &OsStr and a &Vec<String> to save into a TXT
 /// Returns an error if one occurs
 pub fn save_bpe_encoding(fpath: &OsStr, to_save: &Vec<Vec<String>>) -> Result<(), Box<dyn Error>> {
@@ -333,3 +959,232 @@ pub fn save_bpe_encoding(fpath: &OsStr, to_save: &Vec<Vec<String>>) -> Result<()
     }
     Ok(())
 }
+
+/// Checks padding, each truncation mode, and overflow windows of bpe_batch_encoding() on a small vocab.
+#[test]
+fn test_bpe_batch_encoding() {
+    let to_lines = |lines: &[&[&str]]| -> Vec<Vec<String>> {
+        lines
+            .iter()
+            .map(|line| line.iter().map(|s| s.to_string()).collect())
+            .collect()
+    };
+    let text = to_lines(&[&["this", "is", "a", "test"], &["is"]]);
+    let vocab = to_lines(&[&["this", "w"], &["is", "w"], &["test", "w"]]);
+
+    let tail = bpe_batch_encoding(text.clone(), vocab.clone(), 3, Truncation::Tail, 1);
+    assert_eq!(tail.ids, vec![vec![2, 3, 1], vec![3, 0, 0]]);
+    assert_eq!(tail.attention_mask, vec![vec![1, 1, 1], vec![1, 0, 0]]);
+
+    let head = bpe_batch_encoding(text.clone(), vocab.clone(), 3, Truncation::Head, 1);
+    assert_eq!(head.ids[0], vec![3, 1, 4]);
+
+    let middle = bpe_batch_encoding(text, vocab, 3, Truncation::Middle, 1);
+    assert_eq!(middle.ids[0], vec![2, 3, 4]);
+
+    // Only the long sequence overflows, starting stride tokens before the dropped tokens.
+    assert_eq!(
+        middle.overflow,
+        vec![OverflowWindow {
+            sequence: 0,
+            ids: vec![3, 1, 4],
+            attention_mask: vec![1, 1, 1],
+        }]
+    );
+    assert_eq!(head.overflow[0].ids, vec![2, 3, 1]);
+    assert_eq!(tail.overflow[0].ids, vec![1, 4, 0]);
+
+    // Every token is kept or in an overflow window, whatever is truncated.
+    use std::collections::HashSet;
+    let words: Vec<String> = ('a'..='m').map(|c| c.to_string()).collect();
+    let vocab: Vec<Vec<String>> = words
+        .iter()
+        .map(|word| vec![word.clone(), "w".to_string()])
+        .collect();
+    let all_ids: HashSet<u32> = (2..2 + words.len() as u32).collect();
+    for truncation in [Truncation::Head, Truncation::Tail, Truncation::Middle] {
+        for max_len in 1..6 {
+            for stride in 0..max_len {
+                let batch = bpe_batch_encoding(
+                    vec![words.clone()],
+                    vocab.clone(),
+                    max_len,
+                    truncation,
+                    stride,
+                );
+                let mut covered: HashSet<u32> = HashSet::new();
+                let windows = batch.overflow.iter().map(|w| (&w.ids, &w.attention_mask));
+                for (ids, mask) in
+                    std::iter::once((&batch.ids[0], &batch.attention_mask[0])).chain(windows)
+                {
+                    covered.extend(
+                        ids.iter()
+                            .zip(mask)
+                            .filter(|(_, m)| **m == 1)
+                            .map(|(id, _)| *id),
+                    );
+                }
+                assert_eq!(
+                    covered, all_ids,
+                    "{:?} with max_len {} and stride {}",
+                    truncation, max_len, stride
+                );
+            }
+        }
+    }
+}
+
+/// Checks that bpe_encoding_cached() segments words by longest vocab match,
+/// and only segments each distinct word once.
+#[test]
+fn test_bpe_encoding_cached() {
+    let text: Vec<Vec<String>> = vec![
+        vec!["tests".to_string(), "test".to_string()],
+        vec!["tests".to_string(), "xs".to_string()],
+    ];
+    let vocab: Vec<Vec<String>> = vec![
+        vec!["t".to_string()],
+        vec!["s".to_string()],
+        vec!["test".to_string()],
+        vec!["test".to_string(), "w".to_string()],
+    ];
+    let cache = BpeCache::new(8);
+    let encoded = bpe_encoding_cached(text, vocab, &cache);
+
+    assert_eq!(encoded[0], ["test", "s", "test</w>"]);
+    assert_eq!(encoded[1], ["test", "s", UNKNOWN_TOKEN, "s"]);
+    assert_eq!(cache.misses(), 3);
+    assert_eq!(cache.hits(), 1);
+    assert_eq!(cache.len(), 3);
+
+    // Once full, the oldest word is evicted.
+    let small = BpeCache::new(1);
+    small.get_or_insert_with("a", Vec::new);
+    small.get_or_insert_with("b", Vec::new);
+    small.get_or_insert_with("a", Vec::new);
+    assert_eq!(small.hits(), 0);
+    assert_eq!(small.len(), 1);
+}
+
+/// Checks that case markers survive training as whole tokens, and that decoding restores casing.
+#[test]
+fn test_bpe_case_markers() {
+    let line: Vec<String> = tokenize_line_alphas("Sawyer's working, or IS he???");
+    let marked = case_mark_tokens(&line);
+    assert_eq!(
+        marked,
+        [
+            "<cap>",
+            "sawyer's",
+            "working",
+            "or",
+            "<allcaps>",
+            "is",
+            "he"
+        ]
+    );
+    assert_eq!(case_restore_tokens(&marked), line);
+    assert_eq!(case_mark_tokens(&["iPhone".to_string()]), ["iphone"]);
+
+    let vocab = bpe_training(vec![marked.clone()], 60);
+    assert!(vocab.contains(&CAP_MARKER.to_string()));
+    assert!(vocab.contains(&ALLCAPS_MARKER.to_string()));
+
+    let vocab_lines: Vec<Vec<String>> = vocab
+        .iter()
+        .map(|token| match token.strip_suffix("</w>") {
+            Some(word) => vec![word.to_string(), "w".to_string()],
+            None => vec![token.clone()],
+        })
+        .collect();
+    let encoded = bpe_encoding(vec![marked], vocab_lines);
+    assert_eq!(bpe_decoding(&encoded[0]), line);
+}
+
+// Lossless vocab trained once on test.txt, shared by every case of the round trip property.
+#[cfg(test)]
+fn lossless_test_vocab() -> Vec<String> {
+    static VOCAB: std::sync::OnceLock<Vec<String>> = std::sync::OnceLock::new();
+    VOCAB
+        .get_or_init(|| {
+            let corpus = std::fs::read_to_string("test.txt").unwrap();
+            bpe_training_lossless(vec![tokenize_line_lossless(&corpus)], 120).0
+        })
+        .clone()
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    /// Lossless BPE trained on test.txt rebuilds any input without SPACE_MARKER exactly.
+    #[test]
+    fn prop_bpe_lossless_round_trip(line in "\\PC*|[ a-zA-Z'\t\n(/*)?.]*") {
+        proptest::prop_assume!(!line.contains(SPACE_MARKER));
+        let cache = BpeCache::new(DEFAULT_CACHE_CAPACITY);
+        let encoded =
+            bpe_encoding_lossless(vec![tokenize_line_lossless(&line)], lossless_test_vocab(), &cache);
+        proptest::prop_assert_eq!(detokenize_lossless(&encoded[0]), line);
+    }
+}
+
+/// Checks that BpeTokenizer tokenizes a line the same way as bpe_encoding() on its pre-tokenized words.
+#[test]
+fn test_bpe_tokenizer() {
+    let vocab: Vec<String> = ["test", "s", "this</w>", "is</w>"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    let tokenizer = BpeTokenizer::new(&vocab, BpeMode::Lowercase);
+
+    assert_eq!(
+        tokenizer.tokenize("This tests, this."),
+        ["this</w>", "test", "s", "this</w>"]
+    );
+    assert_eq!(tokenizer.cache().hits(), 1);
+}
+
+/// Compares allocations and time of init_vocab_corpus() against splitting every word of the corpus into letters.
+/// Run with: cargo test --release -- --ignored --nocapture bench_init_vocab_corpus
+#[test]
+#[ignore]
+fn bench_init_vocab_corpus() {
+    use std::{env, time::Instant};
+
+    let mut corpus_path = env::current_dir().unwrap();
+    corpus_path.push("Twitter-sentiment-self-drive-DFE-Training.csv");
+    let lines = parse_csv_to_tokens(corpus_path.as_os_str()).unwrap();
+
+    let before = allocations();
+    let start = Instant::now();
+    let mut word_freq: HashMap<String, u32> = HashMap::new();
+    let mut corpus: HashMap<Vec<String>, u32> = HashMap::new();
+    for token in lines.iter().flatten() {
+        let mut word: Vec<String> = Vec::new();
+        for c in token.to_lowercase().chars() {
+            word.push(c.to_string());
+            *word_freq.entry(c.to_string()).or_insert(0) += 1;
+        }
+        word.push("</w>".to_string());
+        *corpus.entry(word).or_insert(0) += 1;
+    }
+    let per_char_time = start.elapsed();
+    let per_char_allocations = allocations() - before;
+
+    let token_lines = lines.clone();
+    let before = allocations();
+    let start = Instant::now();
+    let (frequency_table, word_counts) = init_vocab_corpus(token_lines, false);
+    let counted_time = start.elapsed();
+    let counted_allocations = allocations() - before;
+
+    assert_eq!(frequency_table.len(), word_freq.len());
+    assert_eq!(word_counts.len(), corpus.len());
+    assert!(counted_allocations < per_char_allocations);
+    println!(
+        "{} words: per char {} allocations in {:?}, counted words first {} allocations in {:?}",
+        lines.iter().flatten().count(),
+        per_char_allocations,
+        per_char_time,
+        counted_allocations,
+        counted_time
+    );
+}
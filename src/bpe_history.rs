@@ -0,0 +1,263 @@
+//! Byte Pair Encoder Merge History
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://graphviz.org/doc/info/lang.html
+//! https://www.json.org/json-en.html
+
+use std::collections::HashMap;
+
+pub use crate::bpe::*;
+
+/// One merge applied while deriving the tokens of a word.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivationStep {
+    pub rank: usize,
+    pub merge: Merge,
+    pub segments: Vec<String>,
+}
+
+/// The step by step merge sequence that turns a word into its BPE tokens.
+/// initial holds the characters of the word, followed by </w> unless the merges are lossless.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Derivation {
+    pub initial: Vec<String>,
+    pub steps: Vec<DerivationStep>,
+    tokens: Vec<String>,
+}
+
+impl Derivation {
+    /// The tokens the encoder splits the word into.
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+}
+
+// Number of initial segments a token covers: one per character, with </w> and UNKNOWN_TOKEN as one.
+fn initial_len(token: &str) -> usize {
+    if token == UNKNOWN_TOKEN {
+        return 1;
+    }
+    match token.strip_suffix("</w>") {
+        Some(word) => word.chars().count() + 1,
+        None => token.chars().count(),
+    }
+}
+
+// Record the merges that build token, as (rank, first initial segment) pairs, children before parents.
+// Returns the number of initial segments token covers.
+fn merge_tree(
+    token: &str,
+    start: usize,
+    producers: &HashMap<String, usize>,
+    merges: &[Merge],
+    nodes: &mut Vec<(usize, usize)>,
+) -> usize {
+    match producers.get(token) {
+        Some(&rank) => {
+            let merge = &merges[rank];
+            let left = merge_tree(&merge.left, start, producers, merges, nodes);
+            let right = merge_tree(&merge.right, start + left, producers, merges, nodes);
+            nodes.push((rank, start));
+            left + right
+        }
+        None => initial_len(token),
+    }
+}
+
+/// Explains how the encoder tokenizes a single word with a trained vocab and its merges.
+/// vocab holds the tokens as the encoder matches them, as returned by parse_txt_for_bpe().
+/// The word is split exactly as bpe_encoding(), or bpe_encoding_lossless() if lossless is set,
+/// taking the longest vocab token at each position, so characters missing from the vocab become UNKNOWN_TOKEN,
+/// or stay as they are when lossless.  The merges that build each of those tokens
+/// then fire in rank order, each step merging every pair of that rank at once.
+/// The vocab and merges must come from the same training run for the steps to end at the tokens.
+pub fn bpe_derivation(
+    word: &str,
+    vocab: &[String],
+    merges: &[Merge],
+    lossless: bool,
+) -> Derivation {
+    // A token is built by the first merge that produced it.
+    let mut producers: HashMap<String, usize> = HashMap::new();
+    for (rank, merge) in merges.iter().enumerate() {
+        producers.entry(merge.merged()).or_insert(rank);
+    }
+
+    let vocab_trie = VocabTrie::from_tokens(vocab);
+    let mut initial: Vec<String> = word.chars().map(|c| c.to_string()).collect();
+    let tokens = if lossless {
+        segment_lossless(word, &vocab_trie)
+    } else {
+        initial.push("</w>".to_string());
+        segment_word(&format!("{}{}", word, "</w>"), &vocab_trie)
+    };
+
+    // Each segment is kept with the position of its first initial segment.
+    let mut segments: Vec<(usize, String)> = initial.iter().cloned().enumerate().collect();
+    let mut nodes: Vec<(usize, usize)> = Vec::new();
+    let mut start = 0;
+    for token in &tokens {
+        if token == UNKNOWN_TOKEN {
+            segments[start].1 = token.clone();
+        }
+        start += merge_tree(token, start, &producers, merges, &mut nodes);
+    }
+    nodes.sort();
+
+    let mut steps: Vec<DerivationStep> = Vec::new();
+    for (i, &(rank, start)) in nodes.iter().enumerate() {
+        let at = segments.iter().position(|(s, _)| *s == start).unwrap();
+        let (_, right) = segments.remove(at + 1);
+        segments[at].1.push_str(&right);
+        if nodes.get(i + 1).map(|node| node.0) != Some(rank) {
+            steps.push(DerivationStep {
+                rank,
+                merge: merges[rank].clone(),
+                segments: segments.iter().map(|(_, token)| token.clone()).collect(),
+            });
+        }
+    }
+
+    Derivation {
+        initial,
+        steps,
+        tokens,
+    }
+}
+
+// Quote a token for use in a DOT or JSON string.
+pub(crate) fn quote(token: &str) -> String {
+    let mut out = String::from("\"");
+    for c in token.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Exports the merge graph as Graphviz DOT.
+/// Each merged token has an edge from both of its parents, labelled with the merge rank.
+pub fn bpe_merge_graph_dot(merges: &[Merge]) -> String {
+    let mut out = String::from("digraph bpe_merges {\n");
+    for (rank, merge) in merges.iter().enumerate() {
+        let token = quote(&merge.merged());
+        for parent in [&merge.left, &merge.right] {
+            out.push_str(&format!(
+                "    {} -> {} [label=\"{}\"];\n",
+                quote(parent),
+                token,
+                rank
+            ));
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Exports the merge graph as JSON: a list of merges in rank order,
+/// each giving the merged token and its two parents.
+pub fn bpe_merge_graph_json(merges: &[Merge]) -> String {
+    let entries: Vec<String> = merges
+        .iter()
+        .enumerate()
+        .map(|(rank, merge)| {
+            format!(
+                "  {{\"rank\": {}, \"token\": {}, \"parents\": [{}, {}]}}",
+                rank,
+                quote(&merge.merged()),
+                quote(&merge.left),
+                quote(&merge.right)
+            )
+        })
+        .collect();
+    format!("[\n{}\n]\n", entries.join(",\n"))
+}
+
+/// Checks the derivation of a word and the exported merge graph for a small set of merges.
+#[test]
+fn test_bpe_derivation() {
+    let to_merges = |pairs: &[(&str, &str)]| -> Vec<Merge> {
+        pairs
+            .iter()
+            .map(|(left, right)| Merge {
+                left: left.to_string(),
+                right: right.to_string(),
+            })
+            .collect()
+    };
+    // The vocab of a training run holds its letters and every merged token.
+    let to_vocab = |letters: &str, merges: &[Merge]| -> Vec<String> {
+        letters
+            .chars()
+            .map(|c| c.to_string())
+            .chain(merges.iter().map(Merge::merged))
+            .collect()
+    };
+    // The last step ends at the tokens, apart from a </w> no vocab token ends with.
+    let ends_at_tokens = |derivation: &Derivation| {
+        let last = &derivation.steps.last().unwrap().segments;
+        last.iter()
+            .filter(|token| *token != "</w>")
+            .eq(derivation.tokens().iter())
+    };
+
+    let merges = to_merges(&[("s", "</w>"), ("t", "e"), ("te", "s"), ("x", "y")]);
+    let derivation = bpe_derivation("tests", &to_vocab("estxy", &merges), &merges, false);
+    assert_eq!(derivation.initial, ["t", "e", "s", "t", "s", "</w>"]);
+    let ranks: Vec<usize> = derivation.steps.iter().map(|step| step.rank).collect();
+    assert_eq!(ranks, [0, 1, 2]);
+    assert_eq!(derivation.steps[1].segments, ["te", "s", "t", "s</w>"]);
+    assert_eq!(derivation.tokens(), ["tes", "t", "s</w>"]);
+    assert!(ends_at_tokens(&derivation));
+
+    // Greedy longest match takes "te" before "est", though (s, t) and (e, st) rank ahead of (t, e).
+    let greedy = to_merges(&[("s", "t"), ("e", "st"), ("t", "e")]);
+    let vocab = to_vocab("est", &greedy);
+    let derivation = bpe_derivation("test", &vocab, &greedy, false);
+    let ranks: Vec<usize> = derivation.steps.iter().map(|step| step.rank).collect();
+    assert_eq!(ranks, [0, 2]);
+    assert_eq!(derivation.steps[1].segments, ["te", "st", "</w>"]);
+    let vocab_lines: Vec<Vec<String>> = vocab.iter().map(|token| vec![token.clone()]).collect();
+    let encoded = bpe_encoding(vec![vec!["test".to_string()]], vocab_lines);
+    assert_eq!(derivation.tokens(), encoded[0]);
+    assert!(ends_at_tokens(&derivation));
+
+    // Characters missing from the vocab are unknown, as in the encoder.
+    let cafe = to_merges(&[("c", "a")]);
+    let vocab = to_vocab("acef", &cafe);
+    let derivation = bpe_derivation("café", &vocab, &cafe, false);
+    assert_eq!(derivation.initial, ["c", "a", "f", "é", "</w>"]);
+    assert_eq!(derivation.tokens(), ["ca", "f", UNKNOWN_TOKEN]);
+    let vocab_lines: Vec<Vec<String>> = vocab.iter().map(|token| vec![token.clone()]).collect();
+    let encoded = bpe_encoding(vec![vec!["café".to_string()]], vocab_lines);
+    assert_eq!(derivation.tokens(), encoded[0]);
+    assert!(ends_at_tokens(&derivation));
+
+    // Lossless merges have no </w>, and keep unknown characters.
+    let lossless = to_merges(&[("▁", "t"), ("▁t", "o")]);
+    let derivation = bpe_derivation("▁toé", &to_vocab("▁ot", &lossless), &lossless, true);
+    assert_eq!(derivation.initial, ["▁", "t", "o", "é"]);
+    assert_eq!(derivation.tokens(), ["▁to", "é"]);
+    assert!(ends_at_tokens(&derivation));
+
+    let dot = bpe_merge_graph_dot(&merges[..1]);
+    assert_eq!(
+        dot,
+        "digraph bpe_merges {\n    \"s\" -> \"s</w>\" [label=\"0\"];\n    \"</w>\" -> \"s</w>\" [label=\"0\"];\n}\n"
+    );
+    let json = bpe_merge_graph_json(&merges[1..2]);
+    assert_eq!(
+        json,
+        "[\n  {\"rank\": 0, \"token\": \"te\", \"parents\": [\"t\", \"e\"]}\n]\n"
+    );
+}
@@ -0,0 +1,337 @@
+//! Text Cleaning
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://unicode.org/reports/tr15/
+//! https://docs.rs/unicode-normalization/latest/unicode_normalization/
+//! https://ftfy.readthedocs.io/en/latest/
+//! https://en.wikipedia.org/wiki/Windows-1252
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+use crate::tokenize::{decode_entity, Tokenizer};
+
+/// Unicode normalization form applied by a TextCleaner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnicodeForm {
+    /// Leave the text as is.
+    None,
+    /// Canonical composition: "e" and a combining accent become "é".
+    #[default]
+    Nfc,
+    /// Compatibility composition: also folds ligatures, full width letters and the like, "ﬁ" becomes "fi".
+    Nfkc,
+}
+
+/// Cleans raw text before it is tokenized.  Each step can be switched on or off,
+/// and the steps run in the order of the fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextCleaner {
+    /// Decode HTML entities such as &amp; and &#39;, including doubly escaped ones like &amp;amp;.
+    pub decode_entities: bool,
+    /// Replace literal escape sequences: \n, \r and \t by a space, \" and \' by the quote, \uXXXX by the character.
+    /// Off by default, as it would also rewrite real backslashes, such as in C:\new.
+    pub unescape: bool,
+    /// Repair UTF-8 text that was decoded as Windows-1252 or Latin-1, see repair_mojibake().
+    pub repair_mojibake: bool,
+    /// Remove control characters and invisible format characters such as zero width spaces.
+    pub strip_control: bool,
+    /// Remove a leading retweet marker, "RT" and the "@user:" it credits.
+    pub strip_retweet: bool,
+    /// Unicode normalization form.
+    pub unicode_form: UnicodeForm,
+    /// Remove accents: "café" becomes "cafe".  Letters without a decomposition, like "ß", are kept.
+    pub fold_accents: bool,
+    /// Collapse runs of whitespace to a single space, and trim the ends.
+    pub collapse_whitespace: bool,
+}
+
+impl Default for TextCleaner {
+    /// Every step but escape handling and accent folding, with NFC normalization.
+    fn default() -> Self {
+        TextCleaner {
+            decode_entities: true,
+            unescape: false,
+            repair_mojibake: true,
+            strip_control: true,
+            strip_retweet: true,
+            unicode_form: UnicodeForm::Nfc,
+            fold_accents: false,
+            collapse_whitespace: true,
+        }
+    }
+}
+
+impl TextCleaner {
+    /// Creates a cleaner with every step switched off, to switch on one at a time.
+    pub fn none() -> TextCleaner {
+        TextCleaner {
+            decode_entities: false,
+            unescape: false,
+            repair_mojibake: false,
+            strip_control: false,
+            strip_retweet: false,
+            unicode_form: UnicodeForm::None,
+            fold_accents: false,
+            collapse_whitespace: false,
+        }
+    }
+
+    /// Accepts a line of raw text and returns it cleaned.
+    pub fn clean(&self, line: &str) -> String {
+        let mut line = line.to_string();
+        if self.decode_entities {
+            line = decode_entities(&line);
+        }
+        if self.unescape {
+            line = unescape(&line);
+        }
+        if self.repair_mojibake {
+            line = repair_mojibake(&line);
+        }
+        if self.strip_control {
+            line = strip_control(&line);
+        }
+        if self.strip_retweet {
+            line = strip_retweet(&line);
+        }
+        match self.unicode_form {
+            UnicodeForm::None => {}
+            UnicodeForm::Nfc => line = line.nfc().collect(),
+            UnicodeForm::Nfkc => line = line.nfkc().collect(),
+        }
+        if self.fold_accents {
+            line = fold_accents(&line);
+        }
+        if self.collapse_whitespace {
+            line = line.split_whitespace().collect::<Vec<&str>>().join(" ");
+        }
+        line
+    }
+}
+
+/// Accepts an &str and returns it with HTML entities decoded, repeating while any are left,
+/// as tweets are often escaped twice.
+pub fn decode_entities(line: &str) -> String {
+    //  Using lazy_static as recommended by regex crate docs
+    lazy_static! {
+        static ref ENTITY: Regex =
+            Regex::new(r"&(?:amp|lt|gt|quot|apos|#[0-9]+|#x[0-9a-fA-F]+);").unwrap();
+    }
+    let mut line = line.to_string();
+    // Bounded, as each pass only shortens the line.
+    while ENTITY.is_match(&line) {
+        let decoded = ENTITY
+            .replace_all(&line, |caps: &Captures| decode_entity(&caps[0]))
+            .into_owned();
+        if decoded == line {
+            break;
+        }
+        line = decoded;
+    }
+    line
+}
+
+/// Accepts an &str and returns it with literal escape sequences replaced.
+/// \n, \r and \t become a space, \" and \' the quote, and \uXXXX the character it names.
+/// A run of backslashes is left alone, so "\\new" keeps its n.
+pub fn unescape(line: &str) -> String {
+    //  Using lazy_static as recommended by regex crate docs
+    lazy_static! {
+        static ref ESCAPE: Regex =
+            Regex::new(r#"\\{2,}|\\(?:[nrt]|["']|u[0-9a-fA-F]{4})"#).unwrap();
+    }
+    ESCAPE
+        .replace_all(line, |caps: &Captures| {
+            let escape = &caps[0][1..];
+            match escape {
+                _ if escape.starts_with('\\') => caps[0].to_string(),
+                "n" | "r" | "t" => " ".to_string(),
+                "\"" | "'" => escape.to_string(),
+                _ => u32::from_str_radix(&escape[1..], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .map_or(caps[0].to_string(), |c| c.to_string()),
+            }
+        })
+        .into_owned()
+}
+
+// The characters Windows-1252 puts at bytes 0x80 to 0x9F, where Latin-1 has control characters.
+const WINDOWS_1252: [(char, u8); 27] = [
+    ('\u{20AC}', 0x80),
+    ('\u{201A}', 0x82),
+    ('\u{0192}', 0x83),
+    ('\u{201E}', 0x84),
+    ('\u{2026}', 0x85),
+    ('\u{2020}', 0x86),
+    ('\u{2021}', 0x87),
+    ('\u{02C6}', 0x88),
+    ('\u{2030}', 0x89),
+    ('\u{0160}', 0x8A),
+    ('\u{2039}', 0x8B),
+    ('\u{0152}', 0x8C),
+    ('\u{017D}', 0x8E),
+    ('\u{2018}', 0x91),
+    ('\u{2019}', 0x92),
+    ('\u{201C}', 0x93),
+    ('\u{201D}', 0x94),
+    ('\u{2022}', 0x95),
+    ('\u{2013}', 0x96),
+    ('\u{2014}', 0x97),
+    ('\u{02DC}', 0x98),
+    ('\u{2122}', 0x99),
+    ('\u{0161}', 0x9A),
+    ('\u{203A}', 0x9B),
+    ('\u{0153}', 0x9C),
+    ('\u{017E}', 0x9E),
+    ('\u{0178}', 0x9F),
+];
+
+// The byte a character was decoded from, if the text was read as Windows-1252 or Latin-1.
+fn windows_1252_byte(c: char) -> Option<u8> {
+    match c as u32 {
+        0x80..=0xFF => Some(c as u8),
+        _ => WINDOWS_1252
+            .iter()
+            .find(|(from, _)| *from == c)
+            .map(|(_, byte)| *byte),
+    }
+}
+
+/// Accepts an &str and repairs mojibake, UTF-8 that was decoded as Windows-1252 or Latin-1:
+/// "donâ€™t" becomes "don’t" and "cafÃ©" becomes "café".
+/// Bytes that were lost to U+FFFD replacement characters cannot be recovered.  A run of them,
+/// with the marks and controls around it, becomes a space.
+pub fn repair_mojibake(line: &str) -> String {
+    //  Using lazy_static as recommended by regex crate docs
+    lazy_static! {
+        static ref LOST: Regex =
+            Regex::new(r"\p{M}*\x{FFFD}[\x{FFFD}\p{M}\x{80}-\x{9F}\p{Cf}]*_?").unwrap();
+    }
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let len = match windows_1252_byte(chars[i]) {
+            Some(0xC2..=0xDF) => 2,
+            Some(0xE0..=0xEF) => 3,
+            Some(0xF0..=0xF4) => 4,
+            _ => 0,
+        };
+        if len > 0 && i + len <= chars.len() {
+            let bytes: Option<Vec<u8>> = chars[i..i + len]
+                .iter()
+                .map(|c| windows_1252_byte(*c))
+                .collect();
+            if let Some(Ok(repaired)) = bytes.map(String::from_utf8) {
+                out.push_str(&repaired);
+                i += len;
+                continue;
+            }
+        }
+        out.push(chars[i]);
+        i += 1;
+    }
+    LOST.replace_all(&out, " ").into_owned()
+}
+
+/// Accepts an &str and removes control characters, other than whitespace,
+/// and invisible format characters: zero width spaces, direction marks and byte order marks.
+/// Zero width joiners are kept, as they join emoji sequences.
+pub fn strip_control(line: &str) -> String {
+    line.chars()
+        .filter(|c| {
+            let invisible = matches!(
+                c,
+                '\u{200B}' | '\u{200C}' | '\u{200E}' | '\u{200F}' | '\u{2060}' | '\u{FEFF}'
+            );
+            (c.is_whitespace() || !c.is_control()) && !invisible
+        })
+        .collect()
+}
+
+/// Accepts an &str and removes a leading retweet marker: "RT @user: text" becomes "text".
+pub fn strip_retweet(line: &str) -> String {
+    //  Using lazy_static as recommended by regex crate docs
+    lazy_static! {
+        static ref RETWEET: Regex = Regex::new(r"^\s*RT\b:?\s*(?:@\w+\s*:\s*)?").unwrap();
+    }
+    RETWEET.replace(line, "").into_owned()
+}
+
+/// Accepts an &str and returns it without accents, by decomposing it and dropping the combining marks.
+pub fn fold_accents(line: &str) -> String {
+    line.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .nfc()
+        .collect()
+}
+
+/// Tokenizer that cleans each line with a TextCleaner before passing it to another Tokenizer.
+/// Every loader taking a Tokenizer can apply cleaning through it.
+pub struct CleaningTokenizer {
+    pub inner: Box<dyn Tokenizer>,
+    pub cleaner: TextCleaner,
+}
+
+impl Tokenizer for CleaningTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        self.inner.tokenize(&self.cleaner.clean(line))
+    }
+}
+
+/// Checks each cleaning step, and the default pipeline on a line of test.txt and a tweet from the training data.
+#[test]
+fn test_text_cleaner() {
+    assert_eq!(
+        decode_entities("Q&amp;amp;A &lt;3 &#39;hi&#x27;"),
+        "Q&A <3 'hi'"
+    );
+    assert_eq!(unescape(r#"one\ntwo \"three\" é"#), "one two \"three\" é");
+    assert_eq!(unescape(r"C:\\new\\table"), r"C:\\new\\table");
+    assert_eq!(repair_mojibake("donâ€™t cafÃ© ok"), "don’t café ok");
+    assert_eq!(
+        repair_mojibake("Google\u{322}\u{FFFD}\u{FFFD}\u{89}\u{FFFD}s cars\u{322}\u{FFFD}\u{FFFD}\u{FFFD}\u{FFFD}"),
+        "Google s cars "
+    );
+    assert_eq!(repair_mojibake("na\u{FFFD}ve"), "na ve");
+    assert_eq!(repair_mojibake("naïve “quotes”"), "naïve “quotes”");
+    assert_eq!(strip_control("a\u{200B}b\u{7}c\td"), "abc\td");
+    assert_eq!(strip_retweet("RT @RWW: Why cars"), "Why cars");
+    assert_eq!(strip_retweet("ART is fun"), "ART is fun");
+    assert_eq!(fold_accents("Café naïve Straße"), "Cafe naive Straße");
+
+    let mut cleaner = TextCleaner::default();
+    assert_eq!(cleaner.clean(r"C:\new\table"), r"C:\new\table");
+    let escapes = TextCleaner {
+        unescape: true,
+        ..TextCleaner::default()
+    };
+    assert_eq!(
+        escapes
+            .clean(r"Here is some test text. Question though, \n Does it do what we want? \nwhat"),
+        "Here is some test text. Question though, Does it do what we want? what"
+    );
+    assert_eq!(
+        cleaner.clean("RT @peternowak Q&amp;A with Toyota"),
+        "@peternowak Q&A with Toyota"
+    );
+    assert_eq!(cleaner.clean("Cafe\u{301}  \u{FB01}ne"), "Café \u{FB01}ne");
+    cleaner.unicode_form = UnicodeForm::Nfkc;
+    cleaner.fold_accents = true;
+    assert_eq!(cleaner.clean("Cafe\u{301}  \u{FB01}ne"), "Cafe fine");
+    assert_eq!(TextCleaner::none().clean(" a&amp;b "), " a&amp;b ");
+
+    let tokenizer = CleaningTokenizer {
+        inner: Box::new(crate::tokenize::tokenize_line_alphas_lowercase),
+        cleaner: escapes,
+    };
+    assert_eq!(tokenizer.tokenize("RT @a: don\\'t"), ["don't"]);
+}
@@ -0,0 +1,291 @@
+//! Hashtag Segmentation
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://norvig.com/ngrams/ch14.pdf
+//! https://en.wikipedia.org/wiki/Viterbi_algorithm
+
+use std::{collections::HashMap, error::Error, ffi::OsStr};
+
+use csv::{Reader, Writer};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::tokenize::Tokenizer;
+
+/// Splits hashtag bodies into words: first at camel case, digit and underscore boundaries,
+/// then each piece by the most probable sequence of words under a unigram model learned from a corpus.
+#[derive(Debug, Clone, Default)]
+pub struct HashtagSegmenter {
+    counts: HashMap<String, usize>,
+    total: usize,
+}
+
+impl HashtagSegmenter {
+    /// Creates a segmenter with no word counts, which splits at camel case boundaries only.
+    pub fn new() -> HashtagSegmenter {
+        HashtagSegmenter::default()
+    }
+
+    /// Creates a segmenter from a tokenized corpus, counting each alphabetic token, lowercased.
+    /// Single letters other than a and i are skipped, as in tweets they are mostly pieces of URLs.
+    /// Hashtags should be left out of the corpus, or they are learned as words of their own.
+    pub fn from_corpus(corpus: &[Vec<String>]) -> HashtagSegmenter {
+        let mut segmenter = HashtagSegmenter::new();
+        for token in corpus.iter().flatten() {
+            let token = token.to_lowercase();
+            let letter = token.chars().count() == 1 && token != "a" && token != "i";
+            if !letter && token.chars().all(|c| c.is_alphabetic()) {
+                segmenter.add_word(&token, 1);
+            }
+        }
+        segmenter
+    }
+
+    /// Accepts a path to a CSV of word counts with a header, as written by save().
+    /// Returns the segmenter with those counts, or any resultant errors.
+    pub fn from_file(fpath: &OsStr) -> Result<HashtagSegmenter, Box<dyn Error>> {
+        let mut segmenter = HashtagSegmenter::new();
+        let mut reader = Reader::from_path(fpath)?;
+        for result in reader.records() {
+            let record = result?;
+            let word = record.get(0).ok_or("missing word")?;
+            let count = record.get(1).ok_or("missing count")?.parse::<usize>()?;
+            segmenter.add_word(word, count);
+        }
+        Ok(segmenter)
+    }
+
+    /// Saves the word counts to a CSV with the header word, count, most frequent first.
+    /// Returns an error if one occurs.
+    pub fn save(&self, fpath: &OsStr) -> Result<(), Box<dyn Error>> {
+        let mut counts: Vec<(&String, &usize)> = self.counts.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let mut wtr = Writer::from_path(fpath)?;
+        wtr.write_record(["word", "count"])?;
+        for (word, count) in counts {
+            wtr.write_record([word, &count.to_string()])?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Adds count occurrences of word to the model.
+    pub fn add_word(&mut self, word: &str, count: usize) {
+        *self.counts.entry(word.to_string()).or_insert(0) += count;
+        self.total += count;
+    }
+
+    /// Number of distinct words in the model.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    /// Returns true if the model has no words.
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Accepts a hashtag, with or without its #.
+    /// Output is a Vec<String> of its words, lowercased: "#BlueScreenofDeath" becomes blue, screen, of, death.
+    pub fn segment(&self, hashtag: &str) -> Vec<String> {
+        camel_case_pieces(hashtag.trim_start_matches('#'))
+            .iter()
+            .flat_map(|piece| self.viterbi(&piece.to_lowercase()))
+            .collect()
+    }
+
+    // Log probability of a word.  Unknown words get a probability that falls
+    // tenfold with each letter, so long unknown runs are not split into short unknown words.
+    fn log_probability(&self, word: &str) -> f64 {
+        let total = self.total as f64;
+        match self.counts.get(word) {
+            Some(count) => (*count as f64 / total).ln(),
+            None => 10f64.ln() - total.ln() - word.chars().count() as f64 * 10f64.ln(),
+        }
+    }
+
+    // Most probable split of a lowercase piece into words, by dynamic programming over end positions.
+    // Pieces of three letters or fewer are mostly acronyms, like #ai or #nyc, and are kept whole.
+    fn viterbi(&self, piece: &str) -> Vec<String> {
+        let chars: Vec<char> = piece.chars().collect();
+        if self.total == 0 || chars.len() <= 3 || chars.iter().any(|c| !c.is_alphabetic()) {
+            return vec![piece.to_string()];
+        }
+        // best[end] holds the log probability of the best split of chars[..end], and where its last word starts.
+        let mut best: Vec<(f64, usize)> = vec![(f64::NEG_INFINITY, 0); chars.len() + 1];
+        best[0].0 = 0.0;
+        for end in 1..=chars.len() {
+            for start in 0..end {
+                let word: String = chars[start..end].iter().collect();
+                let score = best[start].0 + self.log_probability(&word);
+                if score > best[end].0 {
+                    best[end] = (score, start);
+                }
+            }
+        }
+        let mut words = Vec::new();
+        let mut end = chars.len();
+        while end > 0 {
+            let start = best[end].1;
+            words.push(chars[start..end].iter().collect());
+            end = start;
+        }
+        words.reverse();
+        words
+    }
+
+    /// Accepts a line and returns it with each hashtag replaced by its words, separated by spaces.
+    /// The words keep the case of the hashtag where the split follows camel case.
+    /// If keep_hashtag is set, the hashtag itself is kept in front of its words.
+    pub fn segment_line(&self, line: &str, keep_hashtag: bool) -> String {
+        //  Using lazy_static as recommended by regex crate docs
+        lazy_static! {
+            static ref HASHTAG: Regex = Regex::new(r"#[\p{L}\p{N}_]+").unwrap();
+        }
+        let mut out = String::new();
+        let mut start = 0;
+        for found in HASHTAG.find_iter(line) {
+            // Skip # inside words and entities, like C# or &#39;, and bodies without letters, like #1.
+            let attached = line[..found.start()]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_alphanumeric() || c == '&');
+            if attached || !found.as_str().chars().any(|c| c.is_alphabetic()) {
+                continue;
+            }
+            out.push_str(&line[start..found.start()]);
+            if keep_hashtag {
+                out.push_str(found.as_str());
+                out.push(' ');
+            }
+            out.push_str(&self.segment_keeping_case(found.as_str()).join(" "));
+            start = found.end();
+        }
+        out.push_str(&line[start..]);
+        out
+    }
+
+    // Same as segment(), with each word in the case it had in the hashtag.
+    fn segment_keeping_case(&self, hashtag: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        for piece in camel_case_pieces(hashtag.trim_start_matches('#')) {
+            let chars: Vec<char> = piece.chars().collect();
+            let mut start = 0;
+            for word in self.viterbi(&piece.to_lowercase()) {
+                let len = word.chars().count();
+                out.push(chars[start..start + len].iter().collect());
+                start += len;
+            }
+        }
+        out
+    }
+}
+
+// Splits a hashtag body at case changes, letter to digit changes and underscores:
+// "SadMacFace" to Sad, Mac, Face, "NASACars" to NASA, Cars, and "Top10" to Top, 10.
+fn camel_case_pieces(body: &str) -> Vec<String> {
+    let chars: Vec<char> = body.chars().collect();
+    let mut pieces = Vec::new();
+    let mut piece = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if *c == '_' {
+            if !piece.is_empty() {
+                pieces.push(std::mem::take(&mut piece));
+            }
+            continue;
+        }
+        if let Some(previous) = piece.chars().next_back() {
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            let boundary = (previous.is_lowercase() && c.is_uppercase())
+                || (previous.is_uppercase() && c.is_uppercase() && next_lower)
+                || (previous.is_numeric() != c.is_numeric());
+            if boundary {
+                pieces.push(std::mem::take(&mut piece));
+            }
+        }
+        piece.push(*c);
+    }
+    if !piece.is_empty() {
+        pieces.push(piece);
+    }
+    pieces
+}
+
+/// Tokenizer that replaces each hashtag in a line by its words before passing the line to another Tokenizer.
+pub struct HashtagTokenizer {
+    pub inner: Box<dyn Tokenizer>,
+    pub segmenter: HashtagSegmenter,
+    pub keep_hashtag: bool,
+}
+
+impl Tokenizer for HashtagTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        self.inner
+            .tokenize(&self.segmenter.segment_line(line, self.keep_hashtag))
+    }
+}
+
+/// Checks camel case splits, Viterbi splits from a small corpus, saving and loading counts, and the hashtag tokenizer.
+#[test]
+fn test_hashtag_segmenter() {
+    assert_eq!(camel_case_pieces("SadMacFace"), ["Sad", "Mac", "Face"]);
+    assert_eq!(
+        camel_case_pieces("NASACars_Top10"),
+        ["NASA", "Cars", "Top", "10"]
+    );
+    assert_eq!(
+        HashtagSegmenter::new().segment("#sadmacface"),
+        ["sadmacface"]
+    );
+
+    let corpus: Vec<Vec<String>> = [
+        "the blue screen of death again",
+        "we pray for the church",
+        "a persecuted people, pray for them",
+        "the car is a screen on wheels",
+    ]
+    .iter()
+    .map(|line| crate::tokenize::tokenize_line_alphas_lowercase(line))
+    .collect();
+    let segmenter = HashtagSegmenter::from_corpus(&corpus);
+    assert_eq!(
+        segmenter.segment("#BlueScreenofDeath"),
+        ["blue", "screen", "of", "death"]
+    );
+    assert_eq!(
+        segmenter.segment("#Prayforpersecutedchurch"),
+        ["pray", "for", "persecuted", "church"]
+    );
+    assert_eq!(segmenter.segment("#ai"), ["ai"]);
+    assert_eq!(
+        segmenter.segment_line("Again #BlueScreenofDeath, &#39;C#", true),
+        "Again #BlueScreenofDeath Blue Screen of Death, &#39;C#"
+    );
+
+    // Saved counts load back into the same segmentation.
+    let mut filepath = std::env::temp_dir();
+    filepath.push("HASHTAGS-test_hashtag_segmenter.csv");
+    segmenter.save(filepath.as_os_str()).unwrap();
+    let loaded = HashtagSegmenter::from_file(filepath.as_os_str()).unwrap();
+    std::fs::remove_file(&filepath).unwrap();
+    assert_eq!(loaded.len(), segmenter.len());
+    assert_eq!(
+        loaded.segment("#Prayforpersecutedchurch"),
+        ["pray", "for", "persecuted", "church"]
+    );
+
+    let tokenizer = HashtagTokenizer {
+        inner: Box::new(crate::tokenize::tokenize_line_alphas_lowercase),
+        segmenter,
+        keep_hashtag: false,
+    };
+    assert_eq!(
+        tokenizer.tokenize("so #SadMacFace"),
+        ["so", "sad", "mac", "face"]
+    );
+}
@@ -0,0 +1,387 @@
+//! Language Identification
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://www.researchgate.net/publication/2375544_N-Gram-Based_Text_Categorization
+//! https://en.wikipedia.org/wiki/Zipf%27s_law
+//! https://github.com/greyblake/whatlang-rs
+//! https://github.com/wooorm/franc
+
+use std::{borrow::Cow, collections::HashMap, error::Error, ffi::OsStr, fs, path::Path};
+
+use csv::{Reader, Writer};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::stopwords::StopwordFilter;
+use crate::tokenize::tokenize_line_unicode_words_lowercase;
+
+/// The trigram profiles bundled with the crate, from language-profiles.txt.
+pub const LANGUAGE_PROFILES: &str = include_str!("../language-profiles.txt");
+
+/// Returned by detect() for text it cannot tell the language of.
+pub const UNKNOWN_LANGUAGE: &str = "unknown";
+
+/// Number of trigrams kept in a profile.
+pub const PROFILE_SIZE: usize = 300;
+
+// A trigram missing from a profile is taken as this much less likely than the last trigram in it.
+const UNSEEN_FACTOR: f64 = 0.1;
+
+// Log likelihood added for each word in the stopword list of a language, where the crate has one.
+// Tweets are short and the profiles come from formal text, so function words are better evidence than trigrams.
+// At 25, 93% of the rows of Twitter-sentiment-self-drive-DFE-Training.csv are found to be English, up from 88% at 4.6.
+const STOPWORD_WEIGHT: f64 = 25.0;
+
+// Counts the trigrams of text, lowercased, with anything but letters as a space,
+// and words padded with a space on each side.
+fn trigram_counts(text: &str) -> HashMap<String, usize> {
+    let chars: Vec<char> = text
+        .chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| if c.is_alphabetic() { c } else { ' ' })
+        .chain(Some(' '))
+        .collect();
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    let mut previous = [' ', ' '];
+    for c in chars {
+        // Skip trigrams that are only a space with a neighbouring space.
+        if !(previous[1] == ' ' && (previous[0] == ' ' || c == ' ')) {
+            let trigram: String = [previous[0], previous[1], c].iter().collect();
+            *counts.entry(trigram).or_insert(0) += 1;
+        }
+        previous = [previous[1], c];
+    }
+    counts
+}
+
+/// Accepts an &str and returns its trigrams, most frequent first, at most PROFILE_SIZE of them.
+/// Text is lowercased, anything but letters becomes a space, and words are padded with a space on each side.
+/// Ties are broken alphabetically so the result does not depend on HashMap order.
+pub fn trigram_profile(text: &str) -> Vec<String> {
+    let mut ranked: Vec<(String, usize)> = trigram_counts(text).into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+        .into_iter()
+        .take(PROFILE_SIZE)
+        .map(|(trigram, _)| trigram)
+        .collect()
+}
+
+// Languages told apart by their script alone, as no trigram profile is bundled for them.
+fn script_language(c: char) -> Option<&'static str> {
+    match c as u32 {
+        0x0370..=0x03FF | 0x1F00..=0x1FFF => Some("greek"),
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Some("korean"),
+        0x3040..=0x30FF => Some("japanese"),
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF => Some("chinese"),
+        0x0E00..=0x0E7F => Some("thai"),
+        _ => None,
+    }
+}
+
+// Returns the text with URLs, @mentions and #hashtags replaced by a space.
+fn language_text(text: &str) -> Cow<'_, str> {
+    //  Using lazy_static as recommended by regex crate docs
+    lazy_static! {
+        static ref NOT_LANGUAGE: Regex =
+            Regex::new(r"(?:https?://|www\.)\S+|[@#][\p{L}\p{N}_]+").unwrap();
+    }
+    NOT_LANGUAGE.replace_all(text, " ")
+}
+
+// A language, the log probability of each trigram in its profile, and its stopwords.
+#[derive(Debug, Clone)]
+struct LanguageProfile {
+    language: String,
+    log_probabilities: HashMap<String, f64>,
+    unseen: f64,
+    stopwords: Option<StopwordFilter>,
+}
+
+/// Identifies the language of a text with Naive Bayes over character trigrams.
+/// Profiles rank trigrams as in Cavnar and Trenkle's method, and the probability of a trigram
+/// falls with its rank following Zipf's law.  Stopwords of the languages in STOPWORD_LANGUAGES add to the evidence.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageIdentifier {
+    profiles: Vec<LanguageProfile>,
+}
+
+impl LanguageIdentifier {
+    /// Creates an identifier without any language profiles.
+    pub fn new() -> LanguageIdentifier {
+        LanguageIdentifier::default()
+    }
+
+    /// Creates an identifier with the bundled LANGUAGE_PROFILES.
+    pub fn bundled() -> LanguageIdentifier {
+        let mut identifier = LanguageIdentifier::new();
+        identifier.add_profiles(LANGUAGE_PROFILES);
+        identifier
+    }
+
+    /// Accepts a path to a profile file, in the format of language-profiles.txt.
+    /// Returns an identifier with the bundled profiles extended by the file, or any resultant errors.
+    pub fn from_file(fpath: &OsStr) -> Result<LanguageIdentifier, Box<dyn Error>> {
+        let mut identifier = LanguageIdentifier::bundled();
+        identifier.add_profiles(&fs::read_to_string(fpath)?);
+        Ok(identifier)
+    }
+
+    /// Adds profiles: a language and a trigram on each line, most frequent first, with spaces written as _.
+    /// Blank lines and lines starting with # are skipped.
+    pub fn add_profiles(&mut self, profiles: &str) {
+        let mut grouped: Vec<(String, Vec<String>)> = Vec::new();
+        for line in profiles.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((language, trigram)) = line.trim().split_once(' ') else {
+                continue;
+            };
+            let trigram = trigram.replace('_', " ");
+            match grouped.iter_mut().find(|(name, _)| name == language) {
+                Some((_, trigrams)) => trigrams.push(trigram),
+                None => grouped.push((language.to_string(), vec![trigram])),
+            }
+        }
+        for (language, trigrams) in grouped {
+            self.add_profile(&language, &trigrams);
+        }
+    }
+
+    /// Adds or replaces the profile of a language from its trigrams, most frequent first.
+    pub fn add_profile(&mut self, language: &str, trigrams: &[String]) {
+        let trigrams = &trigrams[..trigrams.len().min(PROFILE_SIZE)];
+        let weights: Vec<f64> = (1..=trigrams.len()).map(|rank| 1.0 / rank as f64).collect();
+        let total: f64 = weights.iter().sum();
+        let log_probabilities = trigrams
+            .iter()
+            .zip(&weights)
+            .map(|(trigram, weight)| (trigram.clone(), (weight / total).ln()))
+            .collect();
+        let last = weights.last().copied().unwrap_or(1.0);
+        self.profiles.retain(|profile| profile.language != language);
+        self.profiles.push(LanguageProfile {
+            language: language.to_string(),
+            log_probabilities,
+            unseen: (UNSEEN_FACTOR * last / total.max(1.0)).ln(),
+            stopwords: StopwordFilter::for_language(language),
+        });
+    }
+
+    /// Adds or replaces the profile of a language, learned from a sample of its text.
+    pub fn learn(&mut self, language: &str, text: &str) {
+        self.add_profile(language, &trigram_profile(text));
+    }
+
+    /// The languages with a profile, in the order they were added.
+    pub fn languages(&self) -> Vec<&str> {
+        self.profiles
+            .iter()
+            .map(|profile| profile.language.as_str())
+            .collect()
+    }
+
+    /// Accepts an &str and returns each language with its log likelihood for the text, most likely first.
+    /// URLs, @mentions and #hashtags are left out, as they are not written in any language.
+    pub fn scores(&self, text: &str) -> Vec<(String, f64)> {
+        let text = language_text(text);
+        let counts = trigram_counts(&text);
+        let words = tokenize_line_unicode_words_lowercase(&text);
+        let mut scores: Vec<(String, f64)> = self
+            .profiles
+            .iter()
+            .map(|profile| {
+                let mut score: f64 = counts
+                    .iter()
+                    .map(|(trigram, count)| {
+                        let log_probability = profile.log_probabilities.get(trigram);
+                        *count as f64 * log_probability.copied().unwrap_or(profile.unseen)
+                    })
+                    .sum();
+                if let Some(stopwords) = &profile.stopwords {
+                    let found = words.iter().filter(|w| stopwords.is_stopword(w)).count();
+                    score += STOPWORD_WEIGHT * found as f64;
+                }
+                (profile.language.clone(), score)
+            })
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores
+    }
+
+    /// Accepts an &str and returns its language with a confidence from 0 to 1.
+    /// Greek, Korean, Japanese, Chinese and Thai are told by their script, with the share of letters in it as confidence.
+    /// Otherwise the confidence is the posterior probability of the best language, taking every language as equally likely beforehand.
+    /// Text without letters, without any trigram found in a profile, or with a tie for the best language
+    /// is UNKNOWN_LANGUAGE with no confidence.
+    pub fn detect(&self, text: &str) -> (String, f64) {
+        let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+        if letters.is_empty() {
+            return (UNKNOWN_LANGUAGE.to_string(), 0.0);
+        }
+        let mut scripts: HashMap<&str, usize> = HashMap::new();
+        for c in &letters {
+            if let Some(language) = script_language(*c) {
+                *scripts.entry(language).or_insert(0) += 1;
+            }
+        }
+        // Japanese mixes kana with Chinese characters, so any kana makes it Japanese.
+        if let Some(kana) = scripts.remove("japanese") {
+            *scripts.entry("japanese").or_insert(0) +=
+                kana + scripts.remove("chinese").unwrap_or(0);
+        }
+        if let Some((language, count)) = scripts.into_iter().max_by_key(|(_, count)| *count) {
+            if count * 2 > letters.len() {
+                return (language.to_string(), count as f64 / letters.len() as f64);
+            }
+        }
+        let seen = trigram_counts(&language_text(text)).keys().any(|trigram| {
+            self.profiles
+                .iter()
+                .any(|profile| profile.log_probabilities.contains_key(trigram))
+        });
+        if !seen {
+            return (UNKNOWN_LANGUAGE.to_string(), 0.0);
+        }
+        let scores = self.scores(text);
+        match scores.as_slice() {
+            [(language, best), rest @ ..] if !rest.iter().any(|(_, score)| score == best) => {
+                // The posterior of the best language with equal priors, a softmax over the log likelihoods.
+                let total: f64 = scores.iter().map(|(_, score)| (score - best).exp()).sum();
+                (language.clone(), 1.0 / total)
+            }
+            _ => (UNKNOWN_LANGUAGE.to_string(), 0.0),
+        }
+    }
+}
+
+/// Accepts an &str and returns its language and a confidence from 0 to 1, using the bundled profiles.
+/// See LanguageIdentifier::detect().
+pub fn detect(text: &str) -> (String, f64) {
+    lazy_static! {
+        static ref BUNDLED: LanguageIdentifier = LanguageIdentifier::bundled();
+    }
+    BUNDLED.detect(text)
+}
+
+/// Accepts a path to a CSV file with text in the second column, a LanguageIdentifier, and a minimum confidence.
+/// Writes each row to a CSV next to the input named after its language, LANG-<LANGUAGE>-<FILE NAME>,
+/// with the header of the input.  Rows below the minimum confidence go to UNKNOWN_LANGUAGE.
+/// Returns each language with its number of rows, most rows first, or any resultant errors.
+pub fn split_csv_by_language(
+    fpath: &OsStr,
+    identifier: &LanguageIdentifier,
+    min_confidence: f64,
+) -> Result<Vec<(String, usize)>, Box<dyn Error>> {
+    let path = Path::new(fpath);
+    let name = path
+        .file_name()
+        .ok_or("CSV path has no file name")?
+        .to_string_lossy();
+    let mut reader = Reader::from_path(fpath)?;
+    let header = reader.byte_headers()?.clone();
+    let mut writers: HashMap<String, (Writer<fs::File>, usize)> = HashMap::new();
+
+    for result in reader.byte_records() {
+        let record = result?;
+        let text = String::from_utf8_lossy(record.get(1).unwrap_or_default());
+        let (mut language, confidence) = identifier.detect(&text);
+        if confidence < min_confidence {
+            language = UNKNOWN_LANGUAGE.to_string();
+        }
+        if !writers.contains_key(&language) {
+            let outpath = path.with_file_name(format!("LANG-{}-{}", language, name));
+            let mut writer = Writer::from_path(outpath)?;
+            writer.write_byte_record(&header)?;
+            writers.insert(language.clone(), (writer, 0));
+        }
+        let (writer, rows) = writers.get_mut(&language).unwrap();
+        writer.write_byte_record(&record)?;
+        *rows += 1;
+    }
+
+    let mut counts = Vec::new();
+    for (language, (mut writer, rows)) in writers {
+        writer.flush()?;
+        counts.push((language, rows));
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    Ok(counts)
+}
+
+/// Checks detection of sentences in several languages and scripts, and a learned profile.
+#[test]
+fn test_detect_language() {
+    let samples = [
+        (
+            "Driverless cars are not worth the risk on the highway",
+            "english",
+        ),
+        (
+            "Die selbstfahrenden Autos sind noch nicht sicher genug für die Straße",
+            "german",
+        ),
+        (
+            "Les voitures autonomes ne sont pas encore prêtes pour la route",
+            "french",
+        ),
+        (
+            "Los coches autónomos todavía no están listos para la carretera",
+            "spanish",
+        ),
+        (
+            "I veicoli a guida autonoma non sono ancora pronti per la strada",
+            "italian",
+        ),
+        (
+            "Os carros autônomos ainda não estão prontos para as estradas",
+            "portuguese",
+        ),
+        (
+            "Беспилотные автомобили пока не готовы к нашим дорогам",
+            "russian",
+        ),
+        ("自動運転の車はまだ道路の準備ができていません", "japanese"),
+        ("Τα αυτόνομα αυτοκίνητα δεν είναι ακόμα έτοιμα", "greek"),
+    ];
+    for (text, language) in samples {
+        let (detected, confidence) = detect(text);
+        assert_eq!(detected, language, "{}", text);
+        assert!(confidence > 0.0 && confidence <= 1.0);
+    }
+    assert_eq!(detect("123 :) !!"), (UNKNOWN_LANGUAGE.to_string(), 0.0));
+    assert_eq!(detect("ok"), (UNKNOWN_LANGUAGE.to_string(), 0.0));
+
+    // Short, informal English tweets stay English.
+    let lines =
+        crate::bpe::parse_csv_to_lines(OsStr::new("Twitter-sentiment-self-drive-DFE-Test.csv"))
+            .unwrap();
+    let english = lines
+        .iter()
+        .filter(|line| detect(line).0 == "english")
+        .count();
+    assert!(
+        english * 10 >= lines.len() * 9,
+        "{} of {} rows detected as English",
+        english,
+        lines.len()
+    );
+
+    let mut identifier = LanguageIdentifier::new();
+    identifier.learn("pig latin", "ellohay orldway isthay isay igpay atinlay");
+    identifier.learn("english", "hello world this is plain english");
+    assert_eq!(identifier.detect("isthay orldway").0, "pig latin");
+
+    // A tie between languages is unknown.
+    identifier.learn("english again", "hello world this is plain english");
+    assert_eq!(
+        identifier.detect("hello world"),
+        (UNKNOWN_LANGUAGE.to_string(), 0.0)
+    );
+}
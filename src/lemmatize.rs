@@ -0,0 +1,354 @@
+//! Dictionary Lemmatizer
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://wordnet.princeton.edu/documentation/morphy7wn
+//! https://en.wikipedia.org/wiki/Lemmatisation
+
+use std::{collections::HashMap, error::Error, ffi::OsStr, fs};
+
+use crate::negation::map_unnegated;
+use crate::tokenize::Tokenizer;
+
+/// The English lexicon bundled with the crate, from english-lexicon.txt.
+pub const ENGLISH_LEXICON: &str = include_str!("../english-lexicon.txt");
+
+/// Parts of speech the lemmatizer tells apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartOfSpeech {
+    Noun,
+    Verb,
+    Adjective,
+    Adverb,
+}
+
+impl PartOfSpeech {
+    /// Returns the part of speech for a lexicon tag: n, v, a or r.
+    pub fn from_tag(tag: &str) -> Option<PartOfSpeech> {
+        match tag {
+            "n" => Some(PartOfSpeech::Noun),
+            "v" => Some(PartOfSpeech::Verb),
+            "a" => Some(PartOfSpeech::Adjective),
+            "r" => Some(PartOfSpeech::Adverb),
+            _ => None,
+        }
+    }
+}
+
+/// Maps inflected words to their lemma.
+/// Forms in the exception table are looked up first, anything else goes through suffix rules:
+/// plurals and third person -s, -ed and -ing for verbs, and -er and -est for adjectives.
+#[derive(Debug, Clone, Default)]
+pub struct Lemmatizer {
+    exceptions: HashMap<String, Vec<(PartOfSpeech, String)>>,
+}
+
+impl Lemmatizer {
+    /// Creates a lemmatizer with an empty exception table, using the suffix rules alone.
+    pub fn new() -> Lemmatizer {
+        Lemmatizer::default()
+    }
+
+    /// Creates a lemmatizer with the bundled ENGLISH_LEXICON.
+    pub fn english() -> Lemmatizer {
+        let mut lemmatizer = Lemmatizer::new();
+        lemmatizer.add_lexicon(ENGLISH_LEXICON);
+        lemmatizer
+    }
+
+    /// Accepts a path to a lexicon file, in the format of english-lexicon.txt.
+    /// Returns a lemmatizer with the bundled English lexicon extended by the file, or any resultant errors.
+    pub fn from_file(fpath: &OsStr) -> Result<Lemmatizer, Box<dyn Error>> {
+        let mut lemmatizer = Lemmatizer::english();
+        lemmatizer.add_lexicon(&fs::read_to_string(fpath)?);
+        Ok(lemmatizer)
+    }
+
+    /// Adds the entries of a lexicon: one "form lemma pos" per line, pos being n, v, a or r.
+    /// Blank lines, lines starting with # and malformed lines are skipped.
+    pub fn add_lexicon(&mut self, lexicon: &str) {
+        for line in lexicon.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            if let [form, lemma, tag] = line.split_whitespace().collect::<Vec<&str>>()[..] {
+                if let Some(pos) = PartOfSpeech::from_tag(tag) {
+                    self.add_exception(form, lemma, pos);
+                }
+            }
+        }
+    }
+
+    /// Adds a single exception.  A form given with itself as the lemma is kept as it is.
+    pub fn add_exception(&mut self, form: &str, lemma: &str, pos: PartOfSpeech) {
+        self.exceptions
+            .entry(form.to_lowercase())
+            .or_default()
+            .push((pos, lemma.to_lowercase()));
+    }
+
+    /// Number of forms in the exception table.
+    pub fn len(&self) -> usize {
+        self.exceptions.len()
+    }
+
+    /// Returns true if the exception table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.exceptions.is_empty()
+    }
+
+    /// Returns the lemma of a word, which is lowercased first.
+    /// With a part of speech only its exceptions and rules apply.
+    /// Without one, the first exception listed for the word is taken,
+    /// otherwise -ing and -ed are treated as verbs and -s as a plural, and other words are kept.
+    pub fn lemmatize(&self, word: &str, pos: Option<PartOfSpeech>) -> String {
+        let word = word.to_lowercase();
+        if let Some(entries) = self.exceptions.get(&word) {
+            let entry = match pos {
+                Some(pos) => entries.iter().find(|(p, _)| *p == pos),
+                None => entries.first(),
+            };
+            if let Some((_, lemma)) = entry {
+                return lemma.clone();
+            }
+        }
+
+        let lemma = match pos {
+            Some(PartOfSpeech::Noun) => plural_rule(&word),
+            Some(PartOfSpeech::Verb) => verb_rule(&word).or_else(|| plural_rule(&word)),
+            Some(PartOfSpeech::Adjective) => adjective_rule(&word),
+            Some(PartOfSpeech::Adverb) => None,
+            None => verb_rule(&word).or_else(|| plural_rule(&word)),
+        };
+        lemma.unwrap_or(word)
+    }
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u')
+}
+
+// Counts the groups of consecutive vowels, as a rough syllable count.
+fn vowel_groups(word: &str) -> usize {
+    let chars: Vec<char> = word.chars().collect();
+    (0..chars.len())
+        .filter(|&i| is_vowel(chars[i]) && (i == 0 || !is_vowel(chars[i - 1])))
+        .count()
+}
+
+// Nouns: cities -> city, boxes -> box, cars -> car.
+fn plural_rule(word: &str) -> Option<String> {
+    let len = word.chars().count();
+    if len <= 3
+        || !word.ends_with('s')
+        || ["ss", "us", "is", "'s"].iter().any(|s| word.ends_with(s))
+    {
+        return None;
+    }
+    if let Some(stem) = word.strip_suffix("ies") {
+        return Some(if len > 4 {
+            format!("{}y", stem)
+        } else {
+            format!("{}ie", stem)
+        });
+    }
+    for suffix in ["sses", "xes", "zzes", "ches", "shes", "oes"] {
+        if word.ends_with(suffix) {
+            return Some(word[..word.len() - 2].to_string());
+        }
+    }
+    Some(word[..word.len() - 1].to_string())
+}
+
+// Puts back what an -ed, -ing, -er or -est suffix took from the stem:
+// stopped -> stop, hoped -> hope, cried -> cry.
+fn restore_stem(stem: &str) -> String {
+    let chars: Vec<char> = stem.chars().collect();
+    let n = chars.len();
+    let last = chars[n - 1];
+    let before = if n >= 2 { Some(chars[n - 2]) } else { None };
+
+    // A doubled final consonant was added for the suffix.
+    if n >= 4 && before == Some(last) && "bdgmnprt".contains(last) {
+        return chars[..n - 1].iter().collect();
+    }
+    if n >= 6
+        && before == Some('l')
+        && last == 'l'
+        && !stem.ends_with("all")
+        && !stem.ends_with("ill")
+    {
+        return chars[..n - 1].iter().collect();
+    }
+
+    let needs_e =
+        // Words do not end in v, c or a lone z, and -ue drops its e: arrived, danced, seized, argued.
+        matches!(last, 'v' | 'u' | 'c')
+            || last == 'z' && before != Some('z')
+            // caused, closed, but not kissed
+            || last == 's' && before.is_some_and(is_vowel)
+            // charged, judged, bulged
+            || last == 'g' && matches!(before, Some('r' | 'd' | 'l'))
+            // troubled, settled, cycled
+            || last == 'l' && matches!(before, Some('b' | 'c' | 'd' | 'f' | 'g' | 'k' | 'p' | 't' | 'z'))
+            // related, decided, cured, but not treated or avoided
+            || matches!((before, last), (Some('a'), 't') | (Some('i'), 'd') | (Some('a' | 'i' | 'u'), 'r'))
+                && n >= 3
+                && !is_vowel(chars[n - 3])
+            // One syllable ending in a single vowel and a consonant: hoped, liked, smiled.
+            || vowel_groups(stem) == 1
+                && !is_vowel(last)
+                && !matches!(last, 'w' | 'x' | 'y')
+                && before.is_some_and(is_vowel)
+                && (n == 2 || !is_vowel(chars[n - 3]));
+    if needs_e {
+        return format!("{}e", stem);
+    }
+    stem.to_string()
+}
+
+// Verbs: -ed and -ing.
+fn verb_rule(word: &str) -> Option<String> {
+    if let Some(stem) = word.strip_suffix("ied") {
+        if word.len() > 4 {
+            return Some(format!("{}y", stem));
+        }
+    }
+    if word.ends_with("eed") {
+        return if word.len() > 5 {
+            Some(word[..word.len() - 1].to_string())
+        } else {
+            None
+        };
+    }
+    let stem = word
+        .strip_suffix("ed")
+        .or_else(|| word.strip_suffix("ing"))?;
+    if stem.len() < 2 || vowel_groups(stem) == 0 && !stem.contains('y') {
+        return None;
+    }
+    if stem.len() == 2 || stem.ends_with("ee") || stem.ends_with('e') {
+        return Some(stem.to_string());
+    }
+    Some(restore_stem(stem))
+}
+
+// Adjectives: -er and -est.
+fn adjective_rule(word: &str) -> Option<String> {
+    let stem = word
+        .strip_suffix("est")
+        .or_else(|| word.strip_suffix("er"))?;
+    if stem.len() < 2 {
+        return None;
+    }
+    if let Some(base) = stem.strip_suffix('i') {
+        return Some(format!("{}y", base));
+    }
+    Some(restore_stem(stem))
+}
+
+/// Tokenizer that lemmatizes the output of another Tokenizer, with no part of speech.
+/// Tokens other than words, such as numbers, punctuation and case markers, are passed through.
+pub struct LemmatizingTokenizer {
+    pub inner: Box<dyn Tokenizer>,
+    pub lemmatizer: Lemmatizer,
+}
+
+impl Tokenizer for LemmatizingTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        self.inner
+            .tokenize(line)
+            .iter()
+            .map(|token| {
+                map_unnegated(token, |word| {
+                    if word.chars().all(|c| c.is_alphabetic() || c == '\'') {
+                        self.lemmatizer.lemmatize(word, None)
+                    } else {
+                        word.to_string()
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+/// Checks exceptions, suffix rules with and without a part of speech, and the lemmatizing tokenizer.
+#[test]
+fn test_lemmatize() {
+    let lemmatizer = Lemmatizer::english();
+    assert!(lemmatizer.len() > 300);
+
+    let pairs = [
+        ("went", "go"),
+        ("children", "child"),
+        ("was", "be"),
+        ("cities", "city"),
+        ("boxes", "box"),
+        ("cars", "car"),
+        ("glass", "glass"),
+        ("news", "news"),
+        ("stopped", "stop"),
+        ("hoped", "hope"),
+        ("hopping", "hop"),
+        ("wanted", "want"),
+        ("cried", "cry"),
+        ("agreed", "agree"),
+        ("created", "create"),
+        ("treated", "treat"),
+        ("driving", "drive"),
+        ("troubled", "trouble"),
+        ("caused", "cause"),
+        ("kissed", "kiss"),
+        ("called", "call"),
+        ("travelled", "travel"),
+        ("added", "add"),
+        ("being", "be"),
+        ("seeing", "see"),
+        ("morning", "morning"),
+        ("bed", "bed"),
+        ("crashes", "crash"),
+        ("crashed", "crash"),
+    ];
+    for (word, lemma) in pairs {
+        assert_eq!(lemmatizer.lemmatize(word, None), lemma, "Lemma of {}", word);
+    }
+
+    assert_eq!(
+        lemmatizer.lemmatize("bigger", Some(PartOfSpeech::Adjective)),
+        "big"
+    );
+    assert_eq!(
+        lemmatizer.lemmatize("happiest", Some(PartOfSpeech::Adjective)),
+        "happy"
+    );
+    assert_eq!(
+        lemmatizer.lemmatize("nicer", Some(PartOfSpeech::Adjective)),
+        "nice"
+    );
+    assert_eq!(
+        lemmatizer.lemmatize("better", Some(PartOfSpeech::Adverb)),
+        "well"
+    );
+    assert_eq!(lemmatizer.lemmatize("teacher", None), "teacher");
+    assert_eq!(
+        lemmatizer.lemmatize("leaves", Some(PartOfSpeech::Verb)),
+        "leave"
+    );
+    assert_eq!(
+        lemmatizer.lemmatize("leaves", Some(PartOfSpeech::Noun)),
+        "leaf"
+    );
+
+    let tokenizer = LemmatizingTokenizer {
+        inner: Box::new(crate::tokenize::tokenize_line_alphas_lowercase),
+        lemmatizer,
+    };
+    assert_eq!(
+        tokenizer.tokenize("The children were running"),
+        ["the", "child", "be", "run"]
+    );
+}
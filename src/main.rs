@@ -8,18 +8,45 @@
 //! https://doc.rust-lang.org/reference/
 
 mod bpe;
+mod bpe_history;
+mod clean;
 mod debug_tools;
+mod hashtag;
+mod language;
+mod lemmatize;
 mod naive_bayes;
+mod negation;
+mod ngram;
+mod normalize;
+mod placeholder;
+mod stats;
+mod stem;
+mod stopwords;
 mod tokenize;
+mod trie;
 use std::env;
 use std::error::Error;
 use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
 
 pub use crate::bpe::*;
-pub use crate::debug_tools::*;
+pub use crate::bpe_history::*;
+pub use crate::clean::*;
+pub use crate::hashtag::*;
+pub use crate::language::*;
+pub use crate::lemmatize::*;
 pub use crate::naive_bayes::*;
-pub use crate::tokenize::*;
-use clap::arg;
+pub use crate::negation::*;
+pub use crate::ngram::*;
+pub use crate::normalize::*;
+pub use crate::placeholder::*;
+pub use crate::stats::*;
+pub use crate::stem::*;
+pub use crate::stopwords::*;
+use clap::error::ErrorKind;
+use clap::CommandFactory;
 use clap::Parser;
 use csv::Reader;
 use csv::Writer;
@@ -60,6 +87,106 @@ struct Args {
     /// Train a BPE Tokenizer, and use it to tokenize a text file.
     #[arg(long, num_args = 2, value_names = ["SAMPLE TXT", "VOCAB"])]
     bpe_tokenize: Vec<String>,
+
+    /// Keep casing in --bpe-train and --bpe-tokenize, using <cap> and <allcaps> marker tokens.
+    #[arg(long)]
+    case_markers: bool,
+
+    /// Keep every character in --bpe-train, --bpe-tokenize and --bpe-derive, marking spaces with ▁.
+    #[arg(long, conflicts_with = "case_markers")]
+    lossless: bool,
+
+    /// Tokenizer for the Naive Bayes and BPE commands: line, alphas, alphas-lowercase, case-markers, lossless, unicode, unicode-lowercase, tweet, tweet-lowercase, or bpe:<VOCAB TXT>.
+    /// The lossless tokenizer requires --lossless.
+    #[arg(long, value_name = "NAME")]
+    tokenizer: Option<String>,
+
+    /// Normalize social media spelling in the Naive Bayes and BPE commands: elongated words, slang, contractions and repeated punctuation.
+    #[arg(long, conflicts_with = "lossless")]
+    normalize: bool,
+
+    /// Extra slang dictionary for --normalize, one form and its replacement per line.
+    #[arg(long, value_name = "TXT", requires = "normalize")]
+    slang: Option<String>,
+
+    /// Clean raw text in the Naive Bayes and BPE commands before any other step, a comma separated list of
+    /// entities, escapes, mojibake, control, retweet, nfc, nfkc, accents and whitespace.
+    /// Without a list, every step but escapes and accents, with nfc.
+    #[arg(long, value_name = "STEPS", num_args = 0..=1, default_missing_value = "default")]
+    clean: Option<String>,
+
+    /// Replace items with placeholders in the Naive Bayes and BPE commands, a comma separated list of
+    /// url, user, date and num, or all.
+    #[arg(long, value_name = "KINDS", conflicts_with = "lossless")]
+    placeholders: Option<String>,
+
+    /// Split hashtags into words in the Naive Bayes commands, at camel case and by word frequencies
+    /// learned from the training CSV and saved next to the model.
+    #[arg(long)]
+    hashtags: bool,
+
+    /// Keep each hashtag in front of its words for --hashtags.
+    #[arg(long, requires = "hashtags")]
+    keep_hashtags: bool,
+
+    /// Stopwords to drop in the Naive Bayes commands, a comma separated list of:
+    /// a language (english, french, german, spanish, italian, portuguese, dutch), file:<TXT>,
+    /// or from the training CSV, top:<K> most frequent tokens or df:<FRACTION> of rows a token appears in.
+    #[arg(long, value_name = "LISTS")]
+    stopwords: Option<String>,
+
+    /// Lemmatize tokens in the Naive Bayes and BPE commands with the bundled English lexicon.
+    #[arg(long, conflicts_with = "lossless")]
+    lemmatize: bool,
+
+    /// Prefix tokens after not, no, never or a n't contraction with NOT_ until the next punctuation, in the Naive Bayes commands.
+    #[arg(long)]
+    negation: bool,
+
+    /// Stem tokens in the Naive Bayes commands with the Snowball stemmer for english or german.
+    #[arg(long, value_name = "LANGUAGE")]
+    stem: Option<String>,
+
+    /// N-gram features for the Naive Bayes commands: words:<MIN>-<MAX> or chars:<MIN>-<MAX>, e.g. words:1-2.
+    #[arg(long, value_name = "SPEC")]
+    ngrams: Option<String>,
+
+    /// Print the merge sequence that produces the BPE tokens of a word.
+    #[arg(long, num_args = 3, value_names = ["WORD", "VOCAB TXT", "MERGES TXT"])]
+    bpe_derive: Vec<String>,
+
+    /// Export the BPE merge graph, FORMAT is dot or json.
+    #[arg(long, num_args = 2, value_names = ["MERGES TXT", "FORMAT"])]
+    bpe_graph: Vec<String>,
+
+    /// Split a CSV by the detected language of its text column, into LANG-<LANGUAGE>-<CSV> files.
+    #[arg(long, value_name = "CSV")]
+    split_languages: Option<String>,
+
+    /// Rows of --split-languages detected with less confidence, from 0 to 1, go to LANG-unknown-<CSV>.
+    #[arg(long, value_name = "CONFIDENCE", requires = "split_languages")]
+    min_confidence: Option<f64>,
+
+    /// Split a text file into sentences, printing one per line.
+    #[arg(long, value_name = "TXT")]
+    sentences: Option<String>,
+
+    /// Also learn abbreviations from the file itself for --sentences, Punkt style.
+    #[arg(long, requires = "sentences")]
+    learn_abbreviations: bool,
+
+    /// Print statistics of a labeled CSV, tokenized as for the Naive Bayes commands: rows, classes, tokens per row,
+    /// vocabulary, type-token ratio, hapax legomena, top tokens, and Zipf and Heaps fits.
+    #[arg(long, value_name = "CSV")]
+    stats: Option<String>,
+
+    /// Output format for --stats, table or json.  Defaults to table.
+    #[arg(long, value_name = "FORMAT", requires = "stats")]
+    stats_format: Option<String>,
+
+    /// Number of top tokens for --stats, overall and per class.  Defaults to 10.
+    #[arg(long, value_name = "N", requires = "stats")]
+    top: Option<usize>,
 }
 
 /// Takes in a filepath as an &OsStr
@@ -81,50 +208,437 @@ fn load_csv(fpath: &OsStr) -> Result<Vec<String>, Box<dyn Error>> {
 
 fn main() {
     let args = Args::parse();
+    check_args(&args).unwrap_or_else(|err| err.exit());
+    let bpe_mode = if args.lossless {
+        BpeMode::Lossless
+    } else if args.case_markers {
+        BpeMode::CaseMarkers
+    } else {
+        BpeMode::Lowercase
+    };
+    let normalizer = match &args.slang {
+        Some(file) => {
+            let mut filepath = env::current_dir().unwrap();
+            filepath.push(file);
+            SocialNormalizer::from_file(filepath.as_os_str())
+                .unwrap_or_else(|_| error("Cannot open slang dictionary."))
+        }
+        None => SocialNormalizer::english(),
+    };
+    let placeholders = args.placeholders.as_deref().map(select_placeholders);
+    let cleaner = args.clean.as_deref().map(select_cleaner);
+    let segmenter = args.hashtags.then(|| select_hashtag_segmenter(&args));
+    // The named tokenizer is built once, so a bpe: vocab is only loaded once for Naive Bayes and BPE.
+    let tokenizer = args.tokenizer.as_deref().map(|name| SharedTokenizer {
+        inner: Rc::from(select_tokenizer(name, bpe_mode)),
+    });
+    let nb_tokenizer = naive_bayes_tokenizer(
+        &args,
+        tokenizer.clone(),
+        &normalizer,
+        placeholders.as_ref(),
+        cleaner,
+        segmenter.as_ref(),
+    );
+    // BPE defaults to the tokenizer of its mode.
+    let mut bpe_tokenizer: Box<dyn Tokenizer> = match tokenizer {
+        Some(tokenizer) => Box::new(tokenizer),
+        None => bpe_mode.tokenizer(),
+    };
+    if args.normalize {
+        bpe_tokenizer = Box::new(NormalizingTokenizer {
+            inner: bpe_tokenizer,
+            normalizer,
+        });
+    }
+    if let Some(placeholders) = placeholders {
+        bpe_tokenizer = Box::new(PlaceholderTokenizer {
+            inner: bpe_tokenizer,
+            normalizer: placeholders,
+        });
+    }
+    if let Some(cleaner) = cleaner {
+        bpe_tokenizer = Box::new(CleaningTokenizer {
+            inner: bpe_tokenizer,
+            cleaner,
+        });
+    }
+    if args.lemmatize {
+        bpe_tokenizer = Box::new(LemmatizingTokenizer {
+            inner: bpe_tokenizer,
+            lemmatizer: Lemmatizer::english(),
+        });
+    }
 
     if !args.nb_gen.is_empty() {
-        naive_bayes_generate(args.nb_gen.get(0).unwrap(), args.nb_gen.get(1).unwrap());
+        naive_bayes_generate(
+            args.nb_gen.first().unwrap(),
+            args.nb_gen.get(1).unwrap(),
+            nb_tokenizer.as_ref(),
+            segmenter.as_ref(),
+        );
     }
 
     if !args.nb_gen_test.is_empty() {
         naive_bayes_generate_and_test(
-            args.nb_gen_test.get(0).unwrap(),
+            args.nb_gen_test.first().unwrap(),
             args.nb_gen_test.get(1).unwrap(),
             args.nb_gen_test.get(2).unwrap(),
+            nb_tokenizer.as_ref(),
+            segmenter.as_ref(),
         )
     }
 
     if !args.nb_pred_s.is_empty() {
         naive_bayes_predict_string(
-            args.nb_pred_s.get(0).unwrap(),
+            args.nb_pred_s.first().unwrap(),
             args.nb_pred_s.get(1).unwrap(),
+            nb_tokenizer.as_ref(),
         )
     }
 
     if !args.nb_pred.is_empty() {
-        naive_bayes_predict(args.nb_pred.get(0).unwrap(), args.nb_pred.get(1).unwrap())
+        naive_bayes_predict(
+            args.nb_pred.first().unwrap(),
+            args.nb_pred.get(1).unwrap(),
+            nb_tokenizer.as_ref(),
+        )
     }
 
     if !args.bpe_train.is_empty() {
         bpe_generate(
-            args.bpe_train.get(0).unwrap(),
+            args.bpe_train.first().unwrap(),
             args.bpe_train.get(1).unwrap(),
+            bpe_mode,
+            bpe_tokenizer.as_ref(),
         )
     }
 
     if !args.bpe_tokenize.is_empty() {
         bpe_tokenize(
-            args.bpe_tokenize.get(0).unwrap(),
+            args.bpe_tokenize.first().unwrap(),
             args.bpe_tokenize.get(1).unwrap(),
+            bpe_mode,
+            bpe_tokenizer.as_ref(),
         )
     }
+
+    if !args.bpe_derive.is_empty() {
+        bpe_derive(
+            args.bpe_derive.first().unwrap(),
+            args.bpe_derive.get(1).unwrap(),
+            args.bpe_derive.get(2).unwrap(),
+            bpe_mode,
+        )
+    }
+
+    if !args.bpe_graph.is_empty() {
+        bpe_graph(
+            args.bpe_graph.first().unwrap(),
+            args.bpe_graph.get(1).unwrap(),
+        )
+    }
+
+    if let Some(infile) = &args.sentences {
+        sentences(infile, args.learn_abbreviations)
+    }
+
+    if let Some(infile) = &args.split_languages {
+        split_languages(infile, args.min_confidence.unwrap_or(0.0))
+    }
+
+    if let Some(infile) = &args.stats {
+        corpus_stats(
+            infile,
+            args.stats_format.as_deref().unwrap_or("table"),
+            args.top.unwrap_or(10),
+            nb_tokenizer.as_ref(),
+        )
+    }
+}
+
+/// Takes in the parsed arguments.
+/// Returns a usage error for combinations clap cannot express, such as the lossless tokenizer without --lossless,
+/// which would hand BPE tokens its vocab was not trained on.
+fn check_args(args: &Args) -> Result<(), clap::Error> {
+    if args.tokenizer.as_deref() == Some("lossless") && !args.lossless {
+        return Err(Args::command().error(
+            ErrorKind::MissingRequiredArgument,
+            "--tokenizer lossless requires --lossless",
+        ));
+    }
+    Ok(())
+}
+
+/// Takes in the parsed arguments, the tokenizer named by --tokenizer, the normalizer, placeholders and cleaner shared with BPE,
+/// and the HashtagSegmenter for --hashtags.
+/// Returns the Tokenizer for the Naive Bayes commands, wrapped in each preprocessing step the arguments ask for.
+/// Naive Bayes defaults to lowercase words.
+fn naive_bayes_tokenizer(
+    args: &Args,
+    tokenizer: Option<SharedTokenizer>,
+    normalizer: &SocialNormalizer,
+    placeholders: Option<&PlaceholderNormalizer>,
+    cleaner: Option<TextCleaner>,
+    segmenter: Option<&HashtagSegmenter>,
+) -> Box<dyn Tokenizer> {
+    let mut nb_tokenizer: Box<dyn Tokenizer> = match tokenizer {
+        Some(tokenizer) => Box::new(tokenizer),
+        None => Box::new(AlphasLowercaseTokenizer),
+    };
+    if args.normalize {
+        nb_tokenizer = Box::new(NormalizingTokenizer {
+            inner: nb_tokenizer,
+            normalizer: normalizer.clone(),
+        });
+    }
+    if let Some(segmenter) = segmenter {
+        nb_tokenizer = Box::new(HashtagTokenizer {
+            inner: nb_tokenizer,
+            segmenter: segmenter.clone(),
+            keep_hashtag: args.keep_hashtags,
+        });
+    }
+    if let Some(placeholders) = placeholders {
+        nb_tokenizer = Box::new(PlaceholderTokenizer {
+            inner: nb_tokenizer,
+            normalizer: placeholders.clone(),
+        });
+    }
+    // Negation is marked on cleaned, unstemmed words, so negators are found before
+    // stemming and clause ends are found after entities are decoded.
+    if args.negation {
+        nb_tokenizer = Box::new(NegationTokenizer {
+            inner: nb_tokenizer,
+        });
+    }
+    if let Some(cleaner) = cleaner {
+        nb_tokenizer = Box::new(CleaningTokenizer {
+            inner: nb_tokenizer,
+            cleaner,
+        });
+    }
+    if let Some(spec) = &args.stopwords {
+        let training = args
+            .nb_gen
+            .get(1)
+            .or(args.nb_gen_test.get(1))
+            .or(args.stats.as_ref());
+        let mut stopwords = select_stopwords(spec, training, nb_tokenizer.as_ref());
+        if args.negation {
+            stopwords.remove(NEGATORS);
+        }
+        nb_tokenizer = Box::new(StopwordTokenizer {
+            inner: nb_tokenizer,
+            stopwords,
+        });
+    }
+    if args.lemmatize {
+        nb_tokenizer = Box::new(LemmatizingTokenizer {
+            inner: nb_tokenizer,
+            lemmatizer: Lemmatizer::english(),
+        });
+    }
+    if let Some(language) = &args.stem {
+        let language = StemLanguage::from_name(language)
+            .unwrap_or_else(|| error("No stemmer for that language, use english or german."));
+        nb_tokenizer = Box::new(StemmingTokenizer {
+            inner: nb_tokenizer,
+            language,
+        });
+    }
+    if let Some(spec) = &args.ngrams {
+        nb_tokenizer = Box::new(NgramTokenizer {
+            inner: nb_tokenizer,
+            ngrams: select_ngrams(spec),
+        });
+    }
+    nb_tokenizer
+}
+
+/// Takes in a comma separated list of stopword specs as an &str, the training CSV if there is one, and its Tokenizer.
+/// Corpus derived specs tokenize the training CSV, which is assumed to be in the program root folder,
+/// and are skipped when predicting.
+/// Returns the combined StopwordFilter, or exits with an error for a malformed spec.
+fn select_stopwords(
+    spec: &str,
+    training: Option<&String>,
+    tokenizer: &dyn Tokenizer,
+) -> StopwordFilter {
+    let mut stopwords = StopwordFilter::new();
+    for part in spec.split(',') {
+        let filter = match part.split_once(':') {
+            Some(("file", file)) => {
+                let mut filepath = env::current_dir().unwrap();
+                filepath.push(file);
+                StopwordFilter::from_file(filepath.as_os_str())
+                    .unwrap_or_else(|_| error("Cannot open stopword file."))
+            }
+            Some((kind @ ("top" | "df"), value)) => {
+                // Without a training CSV this is a prediction, and the model already lacks these tokens.
+                let Some(training) = training else {
+                    continue;
+                };
+                let mut filepath = env::current_dir().unwrap();
+                filepath.push(training);
+                let corpus = parse_csv_to_tokens_with(filepath.as_os_str(), tokenizer)
+                    .unwrap_or_else(|_| error("Cannot open or parse training CSV."));
+                if kind == "top" {
+                    let k = value
+                        .parse::<usize>()
+                        .unwrap_or_else(|_| error("top:<K> must be a whole number."));
+                    StopwordFilter::from_top_k(&corpus, k)
+                } else {
+                    let max_df = value
+                        .parse::<f64>()
+                        .unwrap_or_else(|_| error("df:<FRACTION> must be a number."));
+                    StopwordFilter::from_document_frequency(&corpus, max_df)
+                }
+            }
+            _ => StopwordFilter::for_language(part)
+                .unwrap_or_else(|| error("Unknown stopword language.")),
+        };
+        stopwords.extend(filter.words());
+    }
+    stopwords
+}
+
+/// Takes in the parsed arguments.
+/// Training learns word frequencies from the training CSV, leaving hashtags, mentions and numbers out of the counts.
+/// Prediction alone loads the counts saved next to the model by training, and --stats learns them from its CSV.
+/// Returns the HashtagSegmenter, which splits at camel case only without any of these commands.
+fn select_hashtag_segmenter(args: &Args) -> HashtagSegmenter {
+    let training = args.nb_gen.get(1).or(args.nb_gen_test.get(1));
+    let model = args.nb_pred_s.get(1).or(args.nb_pred.get(1));
+    if let (None, Some(model)) = (training, model) {
+        let mut filepath = env::current_dir().unwrap();
+        filepath.push(hashtag_counts_name(model));
+        return HashtagSegmenter::from_file(filepath.as_os_str()).unwrap_or_else(|_| {
+            error("Cannot open the hashtag word counts of the model, train it with --hashtags.")
+        });
+    }
+    let Some(corpus) = training.or(args.stats.as_ref()) else {
+        return HashtagSegmenter::new();
+    };
+    let mut filepath = env::current_dir().unwrap();
+    filepath.push(corpus);
+    let words = StructuredTokenizer {
+        keep: vec![TokenKind::Word],
+        normalized: true,
+    };
+    let corpus = parse_csv_to_tokens_with(filepath.as_os_str(), &words)
+        .unwrap_or_else(|_| error("Cannot open or parse CSV for hashtag word frequencies."));
+    HashtagSegmenter::from_corpus(&corpus)
+}
+
+/// Takes in the filename of a model as an &str.
+/// Returns the filename its hashtag word counts are saved under, with 'HASHTAGS-' appended to the front.
+fn hashtag_counts_name(model: &str) -> String {
+    "HASHTAGS-".to_string() + model
+}
+
+/// Takes in a comma separated list of cleaning steps as an &str, or default.
+/// Returns a TextCleaner with those steps, or exits with an error for an unknown step.
+fn select_cleaner(spec: &str) -> TextCleaner {
+    if spec == "default" {
+        return TextCleaner::default();
+    }
+    let mut cleaner = TextCleaner::none();
+    for step in spec.split(',') {
+        match step {
+            "entities" => cleaner.decode_entities = true,
+            "escapes" => cleaner.unescape = true,
+            "mojibake" => cleaner.repair_mojibake = true,
+            "control" => cleaner.strip_control = true,
+            "retweet" => cleaner.strip_retweet = true,
+            "nfc" => cleaner.unicode_form = UnicodeForm::Nfc,
+            "nfkc" => cleaner.unicode_form = UnicodeForm::Nfkc,
+            "accents" => cleaner.fold_accents = true,
+            "whitespace" => cleaner.collapse_whitespace = true,
+            _ => error("Cleaning steps are entities, escapes, mojibake, control, retweet, nfc, nfkc, accents and whitespace."),
+        }
+    }
+    cleaner
+}
+
+/// Takes in a comma separated list of placeholder kinds as an &str: url, user, date, num, or all.
+/// Returns a PlaceholderNormalizer for those kinds, or exits with an error for an unknown kind.
+fn select_placeholders(spec: &str) -> PlaceholderNormalizer {
+    if spec == "all" {
+        return PlaceholderNormalizer::default();
+    }
+    let kinds: Vec<PlaceholderKind> = spec
+        .split(',')
+        .map(|name| {
+            PlaceholderKind::from_name(name)
+                .unwrap_or_else(|| error("Placeholder kinds are url, user, date, num or all."))
+        })
+        .collect();
+    PlaceholderNormalizer::new(&kinds)
+}
+
+/// Takes in an n-gram spec as an &str, words:<MIN>-<MAX> or chars:<MIN>-<MAX>.
+/// Returns the matching Ngrams, or exits with an error for a malformed spec.
+fn select_ngrams(spec: &str) -> Ngrams {
+    let (kind, range) = spec
+        .split_once(':')
+        .unwrap_or_else(|| error("N-gram spec must be words:<MIN>-<MAX> or chars:<MIN>-<MAX>."));
+    let (min, max) = range
+        .split_once('-')
+        .unwrap_or_else(|| error("N-gram range must be <MIN>-<MAX>."));
+    let min = min
+        .parse::<usize>()
+        .unwrap_or_else(|_| error("N-gram range must be numeric."));
+    let max = max
+        .parse::<usize>()
+        .unwrap_or_else(|_| error("N-gram range must be numeric."));
+    if min == 0 || min > max {
+        error("N-gram range must have 1 <= MIN <= MAX.");
+    }
+    match kind {
+        "words" => Ngrams::Words(min, max),
+        "chars" => Ngrams::Chars(min, max),
+        _ => error("N-gram kind must be words or chars."),
+    }
+}
+
+/// Takes in a tokenizer name as an &str, and the BpeMode to use if it names a BPE vocab.
+/// Returns the matching Tokenizer, or exits with an error for an unknown name.
+fn select_tokenizer(name: &str, bpe_mode: BpeMode) -> Box<dyn Tokenizer> {
+    match name {
+        "line" => Box::new(LineTokenizer),
+        "alphas" => Box::new(AlphasTokenizer),
+        "alphas-lowercase" => Box::new(AlphasLowercaseTokenizer),
+        "case-markers" => Box::new(CaseMarkedTokenizer),
+        "lossless" => Box::new(LosslessTokenizer),
+        "unicode" => Box::new(UnicodeTokenizer),
+        "unicode-lowercase" => Box::new(UnicodeWordsLowercaseTokenizer),
+        "tweet" => Box::new(TweetTokenizer { lowercase: false }),
+        "tweet-lowercase" => Box::new(TweetTokenizer { lowercase: true }),
+        _ => {
+            let vocab_file = name
+                .strip_prefix("bpe:")
+                .unwrap_or_else(|| error("Unknown tokenizer name."));
+            let mut vocabpath = env::current_dir().unwrap();
+            vocabpath.push(vocab_file);
+            let tokenizer = BpeTokenizer::from_vocab_file(vocabpath.as_os_str(), bpe_mode)
+                .unwrap_or_else(|_| error("Cannot open or parse tokenizer vocab."));
+            Box::new(tokenizer)
+        }
+    }
 }
 
 /// Takes in a target as an &str and a filename to a training CSV as an &str
 /// Assumes the CSV is in the program root folder.
 /// Builds a Naive Bayes model and saves it.  Resulting CSV is saved to the program root folder.
 /// Its name will be the same as the training file with 'MODEL-' appended to the front.
-fn naive_bayes_generate(target: &str, training: &str) {
+/// The word counts of the HashtagSegmenter, if there is one, are saved next to it for prediction.
+fn naive_bayes_generate(
+    target: &str,
+    training: &str,
+    tokenizer: &dyn Tokenizer,
+    segmenter: Option<&HashtagSegmenter>,
+) {
     let mut filepath = env::current_dir().unwrap();
     let mut savepath = env::current_dir().unwrap();
     filepath.push(training);
@@ -133,20 +647,27 @@ fn naive_bayes_generate(target: &str, training: &str) {
     let ostringpath = filepath.into_os_string();
     let ostringsavepath = savepath.into_os_string();
 
-    let outvec = parse_csv_to_linetarget(&ostringpath)
+    let bayes = bayes_preprocess_csv(&ostringpath, target, tokenizer)
         .unwrap_or_else(|_| error("Cannot open or parse training CSV"));
-    let bayes = bayes_preprocess(&outvec, target);
     let model = generate_naive_bayes_model(&bayes.0, bayes.1);
     save_naive_bayes_model(&ostringsavepath, &model)
         .unwrap_or_else(|_| error("Failed to save model"));
+    save_hashtag_counts(training, segmenter);
 }
 
 /// Takes in a target as an &str, a filename to a training CSV as an &str, and a filename to a test CSV as an &str
 /// Assumes the CSV's are in the program root folder.
 /// Builds a Naive Bayes model and saves it.  Resulting CSV is saved to the program root folder.
 /// Its name will be the same as the training file with 'MODEL-' appended to the front.
+/// The word counts of the HashtagSegmenter, if there is one, are saved next to it for prediction.
 /// Additionally, uses the test CSV to check the model's precision and recall.
-fn naive_bayes_generate_and_test(target: &str, training: &str, test: &str) {
+fn naive_bayes_generate_and_test(
+    target: &str,
+    training: &str,
+    test: &str,
+    tokenizer: &dyn Tokenizer,
+    segmenter: Option<&HashtagSegmenter>,
+) {
     let mut trainpath = env::current_dir().unwrap();
     let mut testpath = env::current_dir().unwrap();
     let mut modelpath = env::current_dir().unwrap();
@@ -158,15 +679,15 @@ fn naive_bayes_generate_and_test(target: &str, training: &str, test: &str) {
     let ostrtestpath = testpath.into_os_string();
     let ostrmodelpath = modelpath.into_os_string();
 
-    let outvec = parse_csv_to_linetarget(&ostrtrainpath)
+    let bayes = bayes_preprocess_csv(&ostrtrainpath, target, tokenizer)
         .unwrap_or_else(|_| error("Cannot open or parse training CSV"));
-    let testvec = parse_csv_to_linetarget(&ostrtestpath)
+    let testvec = parse_csv_to_linetarget_with(&ostrtestpath, tokenizer)
         .unwrap_or_else(|_| error("Cannot open or parse test CSV"));
-    let bayes = bayes_preprocess(&outvec, target);
     let model = generate_naive_bayes_model(&bayes.0, bayes.1);
 
     save_naive_bayes_model(&ostrmodelpath, &model)
         .unwrap_or_else(|_| error("Failed to save model"));
+    save_hashtag_counts(training, segmenter);
 
     let mut total: u32 = 0;
     let mut tpos: u32 = 0;
@@ -197,10 +718,23 @@ fn naive_bayes_generate_and_test(target: &str, training: &str, test: &str) {
     println!("Recall: {}", recall);
 }
 
+/// Takes in a filename to a training CSV as an &str, and the HashtagSegmenter used in training if there is one.
+/// Saves its word counts to the program root folder, named after the model with 'HASHTAGS-' appended to the front.
+fn save_hashtag_counts(training: &str, segmenter: Option<&HashtagSegmenter>) {
+    let Some(segmenter) = segmenter else {
+        return;
+    };
+    let mut savepath = env::current_dir().unwrap();
+    savepath.push(hashtag_counts_name(&("MODEL-".to_string() + training)));
+    segmenter
+        .save(savepath.as_os_str())
+        .unwrap_or_else(|_| error("Failed to save hashtag word counts"));
+}
+
 /// Takes in a target as an &str and a filename to a model CSV as an &str
 /// Assumes the CSV is in the program root folder.
 /// Compares the passed string to the model, and prints whether the prediction matches class_a in the model
-fn naive_bayes_predict_string(sample: &str, model: &str) {
+fn naive_bayes_predict_string(sample: &str, model: &str, tokenizer: &dyn Tokenizer) {
     let mut modelpath = env::current_dir().unwrap();
     modelpath.push(model);
     let ostrmodelpath = modelpath.into_os_string();
@@ -208,7 +742,7 @@ fn naive_bayes_predict_string(sample: &str, model: &str) {
     let model = load_naive_bayes_model(&ostrmodelpath)
         .unwrap_or_else(|_| error("Cannot open or parse model CSV"));
 
-    if naive_bayes_in_class_str(&model, sample) {
+    if naive_bayes_in_class_str_with(&model, sample, tokenizer) {
         println!("Sample is in-class for provided model")
     } else {
         println!("Sample is NOT in-class for provided model")
@@ -219,7 +753,7 @@ fn naive_bayes_predict_string(sample: &str, model: &str) {
 /// Assumes the CSV is in the program root folder.  The sample list must be a single column of sentences and include a header.
 /// Compares each sentence to the model, and saves each class prediction to a CSV paired with the sample sentence.
 /// Resulting file will be the same as the sample file with 'RESULT-' appended.
-fn naive_bayes_predict(sample: &str, model: &str) {
+fn naive_bayes_predict(sample: &str, model: &str, tokenizer: &dyn Tokenizer) {
     let mut modelpath = env::current_dir().unwrap();
     let mut samplepath = env::current_dir().unwrap();
     let mut outpath = env::current_dir().unwrap();
@@ -237,7 +771,7 @@ fn naive_bayes_predict(sample: &str, model: &str) {
     let mut outvec = Vec::new();
 
     for item in samples {
-        if naive_bayes_in_class_str(&model, &item) {
+        if naive_bayes_in_class_str_with(&model, &item, tokenizer) {
             outvec.push((item, "true".to_string()));
         } else {
             outvec.push((item, "false".to_string()));
@@ -268,9 +802,12 @@ fn naive_bayes_predict(sample: &str, model: &str) {
 /// Its name will be the same as the training file with 'BPE-TOKENIZED-' appended to the front.
 /// Select the hyperparameter large enough to form word roots, small enough to separate word parts like pre/suffix.
 /// Can be measured, by percent of tokens in resulting vocab, which end in </w> end of word indicator.
-fn bpe_generate(infile: &str, vocab_size: &str) {
+/// The mode selects whether casing is discarded, kept through <cap> and <allcaps> tokens, or every character is kept.
+/// Each sequence is broken down with the passed tokenizer before training.
+fn bpe_generate(infile: &str, vocab_size: &str, mode: BpeMode, tokenizer: &dyn Tokenizer) {
     let mut filepath = env::current_dir().unwrap();
     let mut savepath = env::current_dir().unwrap();
+    let mut mergespath = env::current_dir().unwrap();
     filepath.push(infile);
     let string_end = &infile[infile.len() - 4..];
     if string_end == ".csv" {
@@ -278,26 +815,43 @@ fn bpe_generate(infile: &str, vocab_size: &str) {
             "BPE-VOCAB-".to_string()
                 + format!("{}{}", &infile[0..infile.len() - 4], ".txt").as_str(),
         );
+        mergespath.push(
+            "BPE-MERGES-".to_string()
+                + format!("{}{}", &infile[0..infile.len() - 4], ".txt").as_str(),
+        );
     } else {
         savepath.push("BPE-VOCAB-".to_string() + infile);
+        mergespath.push("BPE-MERGES-".to_string() + infile);
     }
 
     let ostringpath = filepath.into_os_string();
     let ostringsavepath = savepath.into_os_string();
+    let ostringmergespath = mergespath.into_os_string();
 
-    let outvec = parse_csv_to_tokens(&ostringpath)
+    let outvec = parse_csv_to_tokens_with(&ostringpath, tokenizer)
         .unwrap_or_else(|_| error("Cannot open or parse training CSV."));
 
     // TODO increase n max size, and convert so it can handle in bpe_training:
     // frequency_table.len() < n.into()
     let n = vocab_size.parse::<u8>().unwrap();
-    let vocab = bpe_training(outvec, n);
-    //println!("Lemmatized vocab: {:?}", vocab);
+    let (vocab, merges) = if mode == BpeMode::Lossless {
+        bpe_training_lossless(outvec, n)
+    } else {
+        bpe_training_with_merges(outvec, n)
+    };
 
     save_bpe_vocab(&ostringsavepath, &vocab).unwrap_or_else(|_| error("Failed to save vocab."));
+    save_bpe_merges(&ostringmergespath, &merges)
+        .unwrap_or_else(|_| error("Failed to save merges."));
 }
 
-fn bpe_tokenize(infile: &str, vocab_file: &str) {
+/// Takes in a filename to a sample CSV as an &str and a filename to a vocab TXT as an &str
+/// Assumes both files are in the program root folder.
+/// Tokenizes each sequence with the vocab and saves the result to the program root folder.
+/// Its name will be the same as the sample file with 'BPE-TOKENIZED-' appended to the front.
+/// The mode must match the one the vocab was trained with.
+/// Each sequence is broken down with the passed tokenizer before encoding.
+fn bpe_tokenize(infile: &str, vocab_file: &str, mode: BpeMode, tokenizer: &dyn Tokenizer) {
     let mut filepath = env::current_dir().unwrap();
     let mut savepath = env::current_dir().unwrap();
     let mut vocabpath = env::current_dir().unwrap();
@@ -319,14 +873,195 @@ fn bpe_tokenize(infile: &str, vocab_file: &str) {
     let ostringsavepath = savepath.into_os_string();
     let ostring_vocab_path = vocabpath.into_os_string();
 
-    let outvec = parse_csv_to_tokens(&ostringpath)
-        .unwrap_or_else(|_| error("Cannot open or parse source CSV."));
-    let vocabvec = parse_txt_to_tokens(&ostring_vocab_path)
+    let outvec = parse_csv_to_tokens_with(&ostringpath, tokenizer)
         .unwrap_or_else(|_| error("Cannot open or parse source CSV."));
 
-    let tokenized_file = bpe_encoding(outvec, vocabvec);
-    //bpe_encoding(text_lines: Vec<Vec<String>>, vocab_lines: Vec<Vec<String>>)
+    let cache = BpeCache::new(DEFAULT_CACHE_CAPACITY);
+    let tokenized_file = if mode == BpeMode::Lossless {
+        let vocabvec = parse_txt_to_vocab(&ostring_vocab_path)
+            .unwrap_or_else(|_| error("Cannot open or parse source CSV."));
+        bpe_encoding_lossless(outvec, vocabvec, &cache)
+    } else {
+        let vocabvec = parse_txt_to_tokens(&ostring_vocab_path)
+            .unwrap_or_else(|_| error("Cannot open or parse source CSV."));
+        bpe_encoding_cached(outvec, vocabvec, &cache)
+    };
+    println!(
+        "Word cache: {} hits, {} misses, {:.1}% hit rate",
+        cache.hits(),
+        cache.misses(),
+        cache.hit_rate() * 100.0
+    );
 
     save_bpe_encoding(&ostringsavepath, &tokenized_file)
         .unwrap_or_else(|_| error("Failed to save tokenization."));
 }
+
+/// Takes in a word as an &str, a filename to a vocab TXT as an &str, a filename to a merges TXT as an &str,
+/// and the BpeMode both were trained with.
+/// Assumes the TXTs are in the program root folder.
+/// Prints each merge that fires while tokenizing the word, with its rank and the resulting tokens.
+/// In lossless mode the word is matched with its case kept.
+fn bpe_derive(word: &str, vocab_file: &str, merges_file: &str, mode: BpeMode) {
+    let mut vocabpath = env::current_dir().unwrap();
+    let mut mergespath = env::current_dir().unwrap();
+    vocabpath.push(vocab_file);
+    mergespath.push(merges_file);
+    let ostring_vocab_path = vocabpath.into_os_string();
+    let ostringmergespath = mergespath.into_os_string();
+
+    let vocab = parse_txt_for_bpe(&ostring_vocab_path, mode)
+        .unwrap_or_else(|_| error("Cannot open or parse vocab TXT."));
+    let merges = parse_txt_to_merges(&ostringmergespath)
+        .unwrap_or_else(|_| error("Cannot open or parse merges TXT."));
+    // Training lowercases the corpus unless it is lossless, so the merges only apply to lowercase words.
+    let derivation = if mode == BpeMode::Lossless {
+        bpe_derivation(word, &vocab, &merges, true)
+    } else {
+        bpe_derivation(&word.to_lowercase(), &vocab, &merges, false)
+    };
+
+    println!("Start: {}", derivation.initial.join(" "));
+    for (i, step) in derivation.steps.iter().enumerate() {
+        println!(
+            "Step {}: merge rank {} ({} + {}) -> {}",
+            i + 1,
+            step.rank,
+            step.merge.left,
+            step.merge.right,
+            step.segments.join(" ")
+        );
+    }
+    println!("Tokens: {}", derivation.tokens().join(" "));
+}
+
+/// Takes in a filename to a merges TXT as an &str and an output format of dot or json as an &str
+/// Assumes the TXT is in the program root folder.
+/// Saves the graph of every merged token and its two parents to the program root folder.
+/// Its name will be the merges file name with 'BPE-GRAPH-' in place of 'BPE-MERGES-', and a .dot or .json extension.
+fn bpe_graph(merges_file: &str, format: &str) {
+    let mut mergespath = env::current_dir().unwrap();
+    let mut savepath = env::current_dir().unwrap();
+    mergespath.push(merges_file);
+
+    let stem = Path::new(merges_file)
+        .file_stem()
+        .unwrap_or_else(|| error("Merges file name is empty."))
+        .to_string_lossy()
+        .into_owned();
+    let stem = stem.strip_prefix("BPE-MERGES-").unwrap_or(&stem);
+
+    let ostringmergespath = mergespath.into_os_string();
+    let merges = parse_txt_to_merges(&ostringmergespath)
+        .unwrap_or_else(|_| error("Cannot open or parse merges TXT."));
+
+    let graph = match format {
+        "dot" => bpe_merge_graph_dot(&merges),
+        "json" => bpe_merge_graph_json(&merges),
+        _ => error("Graph format must be dot or json."),
+    };
+    savepath.push(format!("BPE-GRAPH-{}.{}", stem, format));
+
+    fs::write(&savepath, graph).unwrap_or_else(|_| error("Failed to save graph."));
+    println!(
+        "Complete - file saved to: {}",
+        savepath.into_os_string().to_string_lossy()
+    );
+}
+
+/// Takes in a filename to a text file as an &str, and whether to learn abbreviations from it.
+/// Assumes the text file is in the program root folder.
+/// Prints each sentence on its own line.
+fn sentences(infile: &str, learn: bool) {
+    let mut filepath = env::current_dir().unwrap();
+    filepath.push(infile);
+
+    let text = fs::read_to_string(filepath).unwrap_or_else(|_| error("Cannot open text file."));
+    let mut splitter = SentenceSplitter::new();
+    if learn {
+        splitter.learn(&text);
+    }
+    for sentence in splitter.sentences(&text) {
+        println!(
+            "{}",
+            sentence.split_whitespace().collect::<Vec<&str>>().join(" ")
+        );
+    }
+}
+
+/// Takes in a filename to a CSV as an &str, and the minimum confidence to keep a detected language.
+/// Assumes the CSV is in the program root folder, with text in the second column.
+/// Saves the rows of each language to LANG-<LANGUAGE>-<CSV> in the program root folder, and prints the row counts.
+fn split_languages(infile: &str, min_confidence: f64) {
+    let mut filepath = env::current_dir().unwrap();
+    filepath.push(infile);
+
+    let counts = split_csv_by_language(
+        filepath.as_os_str(),
+        &LanguageIdentifier::bundled(),
+        min_confidence,
+    )
+    .unwrap_or_else(|_| error("Cannot open, parse or split CSV."));
+    for (language, rows) in counts {
+        println!("LANG-{}-{}: {} rows", language, infile, rows);
+    }
+}
+
+/// Takes in a filename to a labeled CSV as an &str, an output format of table or json as an &str,
+/// the number of top tokens to list, and the Tokenizer to break down the text.
+/// Assumes the CSV is in the program root folder, with the label in the first column and text in the second.
+/// Prints the statistics of the corpus.
+fn corpus_stats(infile: &str, format: &str, top_n: usize, tokenizer: &dyn Tokenizer) {
+    let mut filepath = env::current_dir().unwrap();
+    filepath.push(infile);
+
+    let stats = CorpusStats::from_csv(filepath.as_os_str(), tokenizer, top_n)
+        .unwrap_or_else(|_| error("Cannot open or parse CSV."));
+    match format {
+        "table" => print!("{}", stats.to_table()),
+        "json" => print!("{}", stats.to_json()),
+        _ => error("Stats format must be table or json."),
+    }
+}
+
+/// Checks negators are marked before stemming, and clauses split after cleaning, with --stem and --negation.
+#[test]
+fn test_naive_bayes_tokenizer_stem_negation() {
+    let args = Args::parse_from([
+        "text_oxidizer",
+        "--stem",
+        "english",
+        "--negation",
+        "--clean",
+    ]);
+    let tokenizer = naive_bayes_tokenizer(
+        &args,
+        None,
+        &SocialNormalizer::english(),
+        None,
+        args.clean.as_deref().map(select_cleaner),
+        None,
+    );
+    assert_eq!(
+        tokenizer.tokenize("Nobody liked the ending &amp; nothing works. Going nowhere"),
+        ["nobodi", "NOT_like", "NOT_the", "NOT_end", "noth", "NOT_work", "go", "nowher"]
+    );
+    assert_eq!(
+        tokenizer.tokenize("Nothing loved, nowhere running"),
+        ["noth", "NOT_love", "nowher", "NOT_run"]
+    );
+}
+
+/// Checks the lossless tokenizer is rejected unless BPE runs in lossless mode.
+#[test]
+fn test_lossless_tokenizer_requires_lossless() {
+    let check = |argv: &[&str]| {
+        let args =
+            Args::try_parse_from(std::iter::once("text_oxidizer").chain(argv.iter().copied()))?;
+        check_args(&args)
+    };
+    assert!(check(&["--tokenizer", "lossless"]).is_err());
+    assert!(check(&["--tokenizer", "lossless", "--case-markers"]).is_err());
+    assert!(check(&["--tokenizer", "lossless", "--lossless"]).is_ok());
+    assert!(check(&["--tokenizer", "tweet"]).is_ok());
+}
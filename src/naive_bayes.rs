@@ -7,10 +7,12 @@
 //! Resources:
 //! https://doc.rust-lang.org/reference/
 
-use std::{collections::HashMap, env, error::Error, ffi::OsStr};
+use std::{borrow::Cow, collections::HashMap, env, error::Error, ffi::OsStr};
 
 use csv::{Reader, Writer};
 
+use crate::ngram::Ngrams;
+
 pub use crate::tokenize::*;
 
 /// Enum used to help calculate Precision and Recall for Naive Bayes
@@ -59,6 +61,16 @@ pub struct NumberWords {
 pub fn bayes_preprocess(
     input: &Vec<LineTarget>,
     target: &str,
+) -> (HashMap<String, TokenOccurence>, NumberWords) {
+    bayes_preprocess_with(input, target, Ngrams::default())
+}
+
+/// Same as bayes_preprocess(), counting the n-grams of each line's tokens as the features.
+/// The model should be used for prediction with an NgramTokenizer of the same Ngrams.
+pub fn bayes_preprocess_with(
+    input: &Vec<LineTarget>,
+    target: &str,
+    ngrams: Ngrams,
 ) -> (HashMap<String, TokenOccurence>, NumberWords) {
     let mut occurence: HashMap<String, TokenOccurence> = HashMap::new();
     let mut numwords = NumberWords {
@@ -66,30 +78,73 @@ pub fn bayes_preprocess(
         class_b: 0,
     };
     for line in input {
-        for token in &line.tokens {
-            if !occurence.contains_key(token) {
-                occurence.insert(
-                    token.clone(),
-                    TokenOccurence {
-                        class_a: 0,
-                        class_b: 0,
-                    },
-                );
-            }
-            let inplace = occurence.get_mut(token).unwrap();
-            if line.target == target {
-                inplace.class_a += 1;
-                numwords.class_a += 1;
-            } else {
-                inplace.class_b += 1;
-                numwords.class_b += 1;
-            }
+        // Unigrams are the tokens themselves, so only build a new Vec for other n-grams.
+        let features: Cow<[String]> = if ngrams == Ngrams::default() {
+            Cow::Borrowed(&line.tokens)
+        } else {
+            Cow::Owned(ngrams.apply(&line.tokens))
+        };
+        for token in features.iter() {
+            count_token(&mut occurence, &mut numwords, token, line.target == target);
         }
     }
 
     (occurence, numwords)
 }
 
+/// Accepts a path to a training CSV, the target to match against and a Tokenizer to break down each line.
+/// Same as bayes_preprocess() over parse_csv_to_linetarget_with(), but counts the tokens as tokens() hands them out,
+/// so no row is collected into a Vec and a token is only copied into a String the first time it is seen.
+pub fn bayes_preprocess_csv(
+    fpath: &OsStr,
+    target: &str,
+    tokenizer: &dyn Tokenizer,
+) -> Result<(HashMap<String, TokenOccurence>, NumberWords), Box<dyn Error>> {
+    let mut occurence: HashMap<String, TokenOccurence> = HashMap::new();
+    let mut numwords = NumberWords {
+        class_a: 0,
+        class_b: 0,
+    };
+    let mut reader = Reader::from_path(fpath)?;
+
+    for result in reader.byte_records() {
+        let record = result?;
+        let in_class = String::from_utf8_lossy(record.get(0).unwrap()) == target;
+        let line = String::from_utf8_lossy(record.get(1).unwrap());
+        for token in tokenizer.tokens(&line) {
+            count_token(&mut occurence, &mut numwords, &token, in_class);
+        }
+    }
+
+    Ok((occurence, numwords))
+}
+
+/// Adds one occurence of a token to its class, copying the token only if it is new to the map.
+fn count_token(
+    occurence: &mut HashMap<String, TokenOccurence>,
+    numwords: &mut NumberWords,
+    token: &str,
+    in_class: bool,
+) {
+    if !occurence.contains_key(token) {
+        occurence.insert(
+            token.to_string(),
+            TokenOccurence {
+                class_a: 0,
+                class_b: 0,
+            },
+        );
+    }
+    let inplace = occurence.get_mut(token).unwrap();
+    if in_class {
+        inplace.class_a += 1;
+        numwords.class_a += 1;
+    } else {
+        inplace.class_b += 1;
+        numwords.class_b += 1;
+    }
+}
+
 ///  Takes in output of the preprocessor - a hashmap of string and occurance pairs, and the class wordcounts.
 ///  Outputs a model in the form of a hashmap of strings and associated class probabilities.
 //  Should look at float math, and the associated i32 variables.  Some datasets may have extremely large wordcount.
@@ -110,18 +165,22 @@ pub fn generate_naive_bayes_model(
     bayes
 }
 
-/// Takes in a naive bayes model in the form of a HashMap<String, TokenProbabilities> and a vec of tokens to check against
+/// Takes in a naive bayes model in the form of a HashMap<String, TokenProbabilities> and the tokens to check against
 /// Returns a raw probability tuple
 /// Intended to be used by naive_bayes_in_class()
-fn generate_naive_bayes_class_probability(
+fn generate_naive_bayes_class_probability<I>(
     model: &HashMap<String, TokenProbabilities>,
-    line: &Vec<String>,
-) -> TokenProbabilities {
+    line: I,
+) -> TokenProbabilities
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
     let mut class_a = 1_f64;
     let mut class_b = 1_f64;
 
     for token in line {
-        if let Some(result) = model.get(token) {
+        if let Some(result) = model.get(token.as_ref()) {
             class_a *= result.class_a;
             class_b *= result.class_b;
         }
@@ -144,8 +203,17 @@ pub fn naive_bayes_in_class(
 /// Takes in a naive bayes model in the form of a HashMap<String, TokenProbabilities> and an &str to check against
 /// Returns true if the probability indicates a match to the target built in to the model.
 pub fn naive_bayes_in_class_str(model: &HashMap<String, TokenProbabilities>, line: &str) -> bool {
-    let result =
-        generate_naive_bayes_class_probability(model, &tokenize_line_alphas_lowercase(line));
+    naive_bayes_in_class_str_with(model, line, &AlphasLowercaseTokenizer)
+}
+
+/// Same as naive_bayes_in_class_str(), tokenizing the &str with the passed Tokenizer.
+/// The Tokenizer should match the one used to train the model.
+pub fn naive_bayes_in_class_str_with(
+    model: &HashMap<String, TokenProbabilities>,
+    line: &str,
+    tokenizer: &dyn Tokenizer,
+) -> bool {
+    let result = generate_naive_bayes_class_probability(model, tokenizer.tokens(line));
 
     result.class_a / result.class_b > 1.0
 }
@@ -236,13 +304,24 @@ pub fn load_naive_bayes_model(
 /// Accepts a path to a CSV file.
 /// Returns a Vec of LineTargets for further processing, or any resultant errors.
 pub fn parse_csv_to_linetarget(fpath: &OsStr) -> Result<Vec<LineTarget>, Box<dyn Error>> {
+    parse_csv_to_linetarget_with(fpath, &AlphasLowercaseTokenizer)
+}
+
+/// Accepts a path to a CSV file and a Tokenizer to break down each line.
+/// Returns a Vec of LineTargets for further processing, or any resultant errors.
+pub fn parse_csv_to_linetarget_with(
+    fpath: &OsStr,
+    tokenizer: &dyn Tokenizer,
+) -> Result<Vec<LineTarget>, Box<dyn Error>> {
     let mut out = Vec::new();
     let mut reader = Reader::from_path(fpath)?;
 
     for result in reader.byte_records() {
         let record = result?;
-        let tokens =
-            tokenize_line_alphas_lowercase(&String::from_utf8_lossy(record.get(1).unwrap()));
+        let tokens = tokenizer
+            .tokens(&String::from_utf8_lossy(record.get(1).unwrap()))
+            .map(Cow::into_owned)
+            .collect();
         let target = String::from_utf8_lossy(record.get(0).unwrap()).into_owned();
 
         let newout = LineTarget { tokens, target };
@@ -294,19 +373,19 @@ fn test_parse_csv_to_linetarget() {
         outvec[1].tokens.len()
     );
 
-    for i in 0..outvec[0].tokens.len() {
+    for (token, expected) in outvec[0].tokens.iter().zip(line_0) {
         assert_eq!(
-            outvec[0].tokens[i], line_0[i],
+            token, expected,
             "Token mismatch - expected {}, got {}",
-            line_0[i], outvec[0].tokens[i]
+            expected, token
         );
     }
 
-    for i in 0..outvec[1].tokens.len() {
+    for (token, expected) in outvec[1].tokens.iter().zip(line_1) {
         assert_eq!(
-            outvec[1].tokens[i], line_1[i],
+            token, expected,
             "Token mismatch - expected {}, got {}",
-            line_1[i], outvec[1].tokens[i]
+            expected, token
         );
     }
 }
@@ -377,6 +456,45 @@ fn test_bayes_preprocess() {
     }
 }
 
+/// Validates bigram features from bayes_preprocess_with() against test.csv
+/// Expects test.csv with proper contents to be in the root directory of the crate.
+#[test]
+fn test_bayes_preprocess_with_ngrams() {
+    let mut filepath = env::current_dir().unwrap();
+    filepath.push("test.csv");
+
+    let outvec = parse_csv_to_linetarget(filepath.as_os_str()).unwrap();
+    let (tokens, words) = bayes_preprocess_with(&outvec, "a", Ngrams::Words(1, 2));
+
+    assert_eq!(tokens.len(), 8);
+    assert_eq!(words.class_a, 5);
+    assert_eq!(words.class_b, 3);
+    assert_eq!(tokens.get("test this").unwrap().class_a, 1);
+    assert_eq!(tokens.get("second line").unwrap().class_b, 1);
+}
+
+/// Validates that bayes_preprocess_csv() counts the same tokens as bayes_preprocess() over the collected lines.
+/// Expects test.csv with proper contents to be in the root directory of the crate.
+#[test]
+fn test_bayes_preprocess_csv() {
+    let mut filepath = env::current_dir().unwrap();
+    filepath.push("test.csv");
+
+    let outvec = parse_csv_to_linetarget(filepath.as_os_str()).unwrap();
+    let (expected, expected_words) = bayes_preprocess(&outvec, "a");
+    let (tokens, words) =
+        bayes_preprocess_csv(filepath.as_os_str(), "a", &AlphasLowercaseTokenizer).unwrap();
+
+    assert_eq!(tokens.len(), expected.len());
+    for (token, counts) in &expected {
+        let streamed = tokens.get(token).unwrap();
+        assert_eq!(streamed.class_a, counts.class_a, "class_a of {}", token);
+        assert_eq!(streamed.class_b, counts.class_b, "class_b of {}", token);
+    }
+    assert_eq!(words.class_a, expected_words.class_a);
+    assert_eq!(words.class_b, expected_words.class_b);
+}
+
 /// Validates output from bayes_preprocess against test.csv
 /// Expects test.csv with proper contents to be in the root directory of the crate.
 /// Future update to create a temporary file with the correct contents and use this to test.
@@ -469,3 +587,78 @@ fn test_naive_bayes_against_test() {
     println!("Total: {}", total);
     println!("Percent correct: {}", percent_correct);
 }
+
+/// Compares allocations and time of training and of prediction with the tokens collected into a Vec, and with borrowed tokens.
+/// Run with: cargo test --release -- --ignored --nocapture bench_naive_bayes_prediction
+#[test]
+#[ignore]
+fn bench_naive_bayes_prediction() {
+    use crate::bpe::{allocations, parse_csv_to_lines};
+    use crate::stopwords::{StopwordFilter, StopwordTokenizer};
+    use std::time::Instant;
+
+    let mut filepath = env::current_dir().unwrap();
+    filepath.push("Twitter-sentiment-self-drive-DFE-Training.csv");
+    let lines = parse_csv_to_lines(filepath.as_os_str()).unwrap();
+    let tokenizer = StopwordTokenizer {
+        inner: Box::new(AlphasLowercaseTokenizer),
+        stopwords: StopwordFilter::for_language("english").unwrap(),
+    };
+
+    let before = allocations();
+    let start = Instant::now();
+    let training = parse_csv_to_linetarget_with(filepath.as_os_str(), &tokenizer).unwrap();
+    let (collected_occurence, _) = bayes_preprocess(&training, "5");
+    let collected_time = start.elapsed();
+    let collected_allocations = allocations() - before;
+
+    let before = allocations();
+    let start = Instant::now();
+    let (occurence, numwords) =
+        bayes_preprocess_csv(filepath.as_os_str(), "5", &tokenizer).unwrap();
+    let borrowed_time = start.elapsed();
+    let borrowed_allocations = allocations() - before;
+
+    assert_eq!(collected_occurence.len(), occurence.len());
+    assert!(borrowed_allocations < collected_allocations);
+    println!(
+        "training on {} lines: collected {} allocations in {:?}, borrowed {} allocations in {:?}",
+        training.len(),
+        collected_allocations,
+        collected_time,
+        borrowed_allocations,
+        borrowed_time
+    );
+
+    let model = generate_naive_bayes_model(&occurence, numwords);
+
+    let before = allocations();
+    let start = Instant::now();
+    let mut collected = Vec::new();
+    for line in &lines {
+        let result = generate_naive_bayes_class_probability(&model, tokenizer.tokenize(line));
+        collected.push(result.class_a / result.class_b > 1.0);
+    }
+    let collected_time = start.elapsed();
+    let collected_allocations = allocations() - before;
+
+    let before = allocations();
+    let start = Instant::now();
+    let mut borrowed = Vec::new();
+    for line in &lines {
+        borrowed.push(naive_bayes_in_class_str_with(&model, line, &tokenizer));
+    }
+    let borrowed_time = start.elapsed();
+    let borrowed_allocations = allocations() - before;
+
+    assert_eq!(collected, borrowed);
+    assert!(borrowed_allocations < collected_allocations);
+    println!(
+        "prediction on {} lines: collected {} allocations in {:?}, borrowed {} allocations in {:?}",
+        lines.len(),
+        collected_allocations,
+        collected_time,
+        borrowed_allocations,
+        borrowed_time
+    );
+}
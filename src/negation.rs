@@ -0,0 +1,137 @@
+//! Negation Scope Marking
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://sentiment.christopherpotts.net/lingstruc.html#negation
+//! https://web.stanford.edu/~jurafsky/slp3/4.pdf
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::tokenize::Tokenizer;
+
+/// Prefix put on tokens in the scope of a negation.
+pub const NEGATION_PREFIX: &str = "NOT_";
+
+/// Words that start a negation scope, besides any word ending in n't.
+/// Apostrophe-less contractions are included, as they are common in tweets.
+pub const NEGATORS: [&str; 24] = [
+    "not", "no", "never", "nor", "neither", "nobody", "none", "nothing", "nowhere", "cannot",
+    "dont", "doesnt", "didnt", "isnt", "arent", "wasnt", "werent", "cant", "couldnt", "wont",
+    "wouldnt", "shouldnt", "aint", "havent",
+];
+
+/// Returns true if token starts a negation scope.
+pub fn is_negator(token: &str) -> bool {
+    let token = token.to_lowercase();
+    NEGATORS.contains(&token.as_str()) || token.ends_with("n't") || token.ends_with("n\u{2019}t")
+}
+
+/// Returns true if token is punctuation ending a clause, and so a negation scope.
+pub fn is_clause_punctuation(token: &str) -> bool {
+    !token.is_empty() && token.chars().all(|c| ".,:;!?".contains(c))
+}
+
+/// Returns the token without its NEGATION_PREFIX, if it has one.
+pub fn unnegated(token: &str) -> &str {
+    token.strip_prefix(NEGATION_PREFIX).unwrap_or(token)
+}
+
+/// Applies f to the token without its NEGATION_PREFIX, then puts the prefix back.
+/// Lets steps after negation marking, such as stemming, see the bare word.
+pub fn map_unnegated(token: &str, f: impl FnOnce(&str) -> String) -> String {
+    match token.strip_prefix(NEGATION_PREFIX) {
+        Some(word) => format!("{}{}", NEGATION_PREFIX, f(word)),
+        None => f(token),
+    }
+}
+
+/// Accepts a slice of tokens.
+/// Output is a Vec<String> with NEGATION_PREFIX on every token after a negator,
+/// up to the next clause punctuation token or the end of the tokens.
+pub fn mark_negation(tokens: &[String]) -> Vec<String> {
+    let mut negated = false;
+    let mut out = Vec::new();
+    for token in tokens {
+        if is_clause_punctuation(token) {
+            negated = false;
+            out.push(token.clone());
+        } else if is_negator(token) {
+            negated = true;
+            out.push(token.clone());
+        } else if negated {
+            out.push(format!("{}{}", NEGATION_PREFIX, token));
+        } else {
+            out.push(token.clone());
+        }
+    }
+    out
+}
+
+/// Tokenizer that marks negation scopes in the output of another Tokenizer.
+/// The line is split into clauses at punctuation followed by whitespace, and each clause
+/// tokenized on its own, so scopes end at punctuation even when the inner Tokenizer drops it.
+pub struct NegationTokenizer {
+    pub inner: Box<dyn Tokenizer>,
+}
+
+impl Tokenizer for NegationTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        //  Using lazy_static as recommended by regex crate docs
+        lazy_static! {
+            static ref CLAUSE_END: Regex = Regex::new(r"[.,:;!?]+(?:\s|$)").unwrap();
+        }
+        let mut out = Vec::new();
+        let mut start = 0;
+        for found in CLAUSE_END.find_iter(line) {
+            out.extend(mark_negation(
+                &self.inner.tokenize(&line[start..found.end()]),
+            ));
+            start = found.end();
+        }
+        out.extend(mark_negation(&self.inner.tokenize(&line[start..])));
+        out
+    }
+}
+
+/// Checks negation scopes end at punctuation, with and without punctuation tokens.
+#[test]
+fn test_mark_negation() {
+    let tokens: Vec<String> = ["it's", "not", "worth", "the", "risk", ",", "sadly"]
+        .iter()
+        .map(|t| t.to_string())
+        .collect();
+    assert_eq!(
+        mark_negation(&tokens),
+        [
+            "it's",
+            "not",
+            "NOT_worth",
+            "NOT_the",
+            "NOT_risk",
+            ",",
+            "sadly"
+        ]
+    );
+
+    let tokenizer = NegationTokenizer {
+        inner: Box::new(crate::tokenize::tokenize_line_alphas_lowercase),
+    };
+    assert_eq!(
+        tokenizer.tokenize("I don't trust it. Worth 2.5 seconds? Never, ever"),
+        [
+            "i",
+            "don't",
+            "NOT_trust",
+            "NOT_it",
+            "worth",
+            "seconds",
+            "never",
+            "ever"
+        ]
+    );
+}
@@ -0,0 +1,114 @@
+//! N-gram Features
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://en.wikipedia.org/wiki/N-gram
+//! https://arxiv.org/abs/1607.04606
+
+use crate::tokenize::Tokenizer;
+
+/// Marks the start of a word in character n-grams.
+pub const WORD_START: char = '<';
+/// Marks the end of a word in character n-grams.
+pub const WORD_END: char = '>';
+
+/// Which n-grams to build from a token stream, each with an inclusive range of n.
+/// Words(1, 1) leaves the tokens as they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ngrams {
+    Words(usize, usize),
+    Chars(usize, usize),
+}
+
+impl Default for Ngrams {
+    fn default() -> Self {
+        Ngrams::Words(1, 1)
+    }
+}
+
+impl Ngrams {
+    /// Builds the n-grams of a token stream.
+    pub fn apply(&self, tokens: &[String]) -> Vec<String> {
+        match *self {
+            Ngrams::Words(min, max) => word_ngrams(tokens, min, max),
+            Ngrams::Chars(min, max) => char_ngrams(tokens, min, max),
+        }
+    }
+}
+
+/// Accepts a slice of tokens and an inclusive range of n.
+/// Output is a Vec<String> of every run of n consecutive tokens joined by a space,
+/// all unigrams first, then all bigrams, and so on.
+pub fn word_ngrams(tokens: &[String], min: usize, max: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    for n in min.max(1)..=max {
+        for window in tokens.windows(n) {
+            out.push(window.join(" "));
+        }
+    }
+    out
+}
+
+/// Accepts a slice of tokens and an inclusive range of n.
+/// Output is a Vec<String> of the character n-grams of each token, padded with WORD_START and WORD_END
+/// so that prefixes and suffixes are told apart from the middle of a word.
+pub fn char_ngrams(tokens: &[String], min: usize, max: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    for token in tokens {
+        let mut chars = vec![WORD_START];
+        chars.extend(token.chars());
+        chars.push(WORD_END);
+        for n in min.max(1)..=max {
+            for window in chars.windows(n) {
+                out.push(window.iter().collect());
+            }
+        }
+    }
+    out
+}
+
+/// Tokenizer that builds n-grams from the output of another Tokenizer,
+/// so a Naive Bayes model sees the same features in training and prediction.
+pub struct NgramTokenizer {
+    pub inner: Box<dyn Tokenizer>,
+    pub ngrams: Ngrams,
+}
+
+impl Tokenizer for NgramTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        self.ngrams.apply(&self.inner.tokenize(line))
+    }
+}
+
+/// Checks word and character n-grams over a short token stream.
+#[test]
+fn test_ngrams() {
+    let tokens: Vec<String> = ["not", "worth", "it"]
+        .iter()
+        .map(|t| t.to_string())
+        .collect();
+
+    assert_eq!(
+        word_ngrams(&tokens, 1, 2),
+        ["not", "worth", "it", "not worth", "worth it"]
+    );
+    assert_eq!(word_ngrams(&tokens, 3, 4), ["not worth it"]);
+    assert_eq!(Ngrams::default().apply(&tokens), tokens);
+    assert_eq!(
+        char_ngrams(&tokens[2..], 2, 3),
+        ["<i", "it", "t>", "<it", "it>"]
+    );
+
+    let tokenizer = NgramTokenizer {
+        inner: Box::new(crate::tokenize::tokenize_line_alphas_lowercase),
+        ngrams: Ngrams::Words(2, 2),
+    };
+    assert_eq!(
+        tokenizer.tokenize("Not worth it."),
+        ["not worth", "worth it"]
+    );
+}
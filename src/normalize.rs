@@ -0,0 +1,228 @@
+//! Social Media Normalization
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://aclanthology.org/P11-1038/
+//! https://github.com/cbaziotis/ekphrasis
+
+use std::{collections::HashMap, error::Error, ffi::OsStr, fs};
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+use crate::tokenize::Tokenizer;
+
+/// The slang and contraction dictionary bundled with the crate, from slang-dictionary.txt.
+pub const SLANG_DICTIONARY: &str = include_str!("../slang-dictionary.txt");
+
+/// Accepts a word and collapses any letter repeated three or more times to two: "soooo" becomes "soo".
+/// Digits are left alone.
+pub fn collapse_elongation(word: &str) -> String {
+    let mut out = String::new();
+    let mut run = 0;
+    let mut previous = None;
+    for c in word.chars() {
+        if Some(c) == previous {
+            run += 1;
+        } else {
+            run = 1;
+            previous = Some(c);
+        }
+        if run <= 2 || !c.is_alphabetic() {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Accepts a line and standardizes runs of punctuation:
+/// "!!!" becomes "!", "???" becomes "?", any mix of the two becomes "?!",
+/// two or more periods become "...", and other repeated marks are kept once.
+pub fn standardize_punctuation(line: &str) -> String {
+    //  Using lazy_static as recommended by regex crate docs
+    lazy_static! {
+        static ref BANGS: Regex = Regex::new(r"[!?]{2,}").unwrap();
+        static ref DOTS: Regex = Regex::new(r"\.{2,}|\u{2026}+").unwrap();
+        static ref REPEATS: Regex = Regex::new(r",{2,}|;{2,}|:{2,}|-{3,}|\*{2,}").unwrap();
+    }
+    let line = BANGS.replace_all(line, |caps: &Captures| {
+        let run = &caps[0];
+        match (run.contains('?'), run.contains('!')) {
+            (true, true) => "?!",
+            (true, false) => "?",
+            _ => "!",
+        }
+    });
+    let line = DOTS.replace_all(&line, "...");
+    REPEATS
+        .replace_all(&line, |caps: &Captures| caps[0][..1].to_string())
+        .into_owned()
+}
+
+/// Normalizes the spelling of social media text before it is tokenized:
+/// collapses elongated words, expands slang and contractions from a dictionary,
+/// and standardizes repeated punctuation.
+#[derive(Debug, Clone, Default)]
+pub struct SocialNormalizer {
+    dictionary: HashMap<String, String>,
+}
+
+impl SocialNormalizer {
+    /// Creates a normalizer with an empty dictionary.
+    pub fn new() -> SocialNormalizer {
+        SocialNormalizer::default()
+    }
+
+    /// Creates a normalizer with the bundled SLANG_DICTIONARY.
+    pub fn english() -> SocialNormalizer {
+        let mut normalizer = SocialNormalizer::new();
+        normalizer.add_dictionary(SLANG_DICTIONARY);
+        normalizer
+    }
+
+    /// Accepts a path to a dictionary file, in the format of slang-dictionary.txt.
+    /// Returns a normalizer with the bundled dictionary extended by the file, or any resultant errors.
+    /// Entries in the file replace bundled entries for the same form.
+    pub fn from_file(fpath: &OsStr) -> Result<SocialNormalizer, Box<dyn Error>> {
+        let mut normalizer = SocialNormalizer::english();
+        normalizer.add_dictionary(&fs::read_to_string(fpath)?);
+        Ok(normalizer)
+    }
+
+    /// Adds the entries of a dictionary: a form, a space and its replacement on each line.
+    /// Blank lines and lines starting with # are skipped.
+    pub fn add_dictionary(&mut self, dictionary: &str) {
+        for line in dictionary.lines() {
+            if line.starts_with('#') {
+                continue;
+            }
+            if let Some((form, replacement)) = line.trim().split_once(' ') {
+                self.add(form, replacement.trim());
+            }
+        }
+    }
+
+    /// Adds a single entry.
+    pub fn add(&mut self, form: &str, replacement: &str) {
+        self.dictionary
+            .insert(form.to_lowercase(), replacement.to_string());
+    }
+
+    /// Number of entries in the dictionary.
+    pub fn len(&self) -> usize {
+        self.dictionary.len()
+    }
+
+    /// Returns true if the dictionary is empty.
+    pub fn is_empty(&self) -> bool {
+        self.dictionary.is_empty()
+    }
+
+    /// Accepts a line and returns it normalized.
+    /// A replaced word keeps its case: "Ur" becomes "Your" and "IDK" becomes "I DO NOT KNOW".
+    /// Words that are part of an abbreviation, URL, mention or hashtag are not replaced.
+    pub fn normalize(&self, line: &str) -> String {
+        //  Using lazy_static as recommended by regex crate docs
+        lazy_static! {
+            static ref WORD: Regex = Regex::new(r"[\p{L}\p{N}][\p{L}\p{N}'\u{2019}]*").unwrap();
+        }
+        let attached = |c: Option<char>| c.is_some_and(|c| "./@#_&=".contains(c));
+        let line = WORD.replace_all(line, |caps: &Captures| {
+            let found = caps.get(0).unwrap();
+            let word = collapse_elongation(&found.as_str().replace('\u{2019}', "'"));
+            if attached(line[..found.start()].chars().next_back())
+                || attached(line[found.end()..].chars().next())
+            {
+                return word;
+            }
+            // A single letter is only slang as a lowercase word standing between two words, as in "see u soon",
+            // so initials, labels such as "Plan B" and list markers such as "n)" are kept.
+            let between_words = || {
+                let before = &line[..found.start()];
+                let after = &line[found.end()..];
+                before.ends_with(char::is_whitespace)
+                    && after.starts_with(char::is_whitespace)
+                    && before.trim_end().ends_with(char::is_alphanumeric)
+                    && after.trim_start().starts_with(char::is_alphanumeric)
+            };
+            if word.chars().count() == 1
+                && !(word.chars().all(char::is_lowercase) && between_words())
+            {
+                return word;
+            }
+            match self.dictionary.get(&word.to_lowercase()) {
+                Some(replacement) => match_case(&word, replacement),
+                None => word,
+            }
+        });
+        standardize_punctuation(&line)
+    }
+}
+
+// Gives replacement the case of word: all caps, capitalized, or as written.
+fn match_case(word: &str, replacement: &str) -> String {
+    let letters: Vec<char> = word.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        replacement.to_uppercase()
+    } else if letters.first().is_some_and(|c| c.is_uppercase()) {
+        let mut chars = replacement.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new(),
+        }
+    } else {
+        replacement.to_string()
+    }
+}
+
+/// Tokenizer that normalizes each line with a SocialNormalizer before passing it to another Tokenizer.
+pub struct NormalizingTokenizer {
+    pub inner: Box<dyn Tokenizer>,
+    pub normalizer: SocialNormalizer,
+}
+
+impl Tokenizer for NormalizingTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        self.inner.tokenize(&self.normalizer.normalize(line))
+    }
+}
+
+/// Checks elongation, slang, contractions, case and punctuation on a sample tweet.
+#[test]
+fn test_social_normalizer() {
+    assert_eq!(collapse_elongation("soooo cooool 1000"), "soo cool 1000");
+    assert_eq!(
+        standardize_punctuation("What?!?! No!!! Wait.... ok,,"),
+        "What?! No! Wait... ok,"
+    );
+
+    let normalizer = SocialNormalizer::english();
+    assert!(normalizer.len() > 100);
+    assert_eq!(
+        normalizer.normalize("Ur car is soooo gr8, dont u think??? IDK"),
+        "Your car is so great, do not you think? I DO NOT KNOW"
+    );
+    // Single letters are kept unless lowercase and between two words.
+    assert_eq!(
+        normalizer.normalize("George R R Martin, Plan B or U: k see u r n) later"),
+        "George R R Martin, Plan B or U: k see you are n) later"
+    );
+    assert_eq!(normalizer.normalize("It\u{2019}s fine"), "It is fine");
+    assert_eq!(
+        normalizer.normalize("U.S. @u see http://t.co/u"),
+        "U.S. @u see http://t.co/u"
+    );
+
+    let tokenizer = NormalizingTokenizer {
+        inner: Box::new(crate::tokenize::tokenize_line_alphas_lowercase),
+        normalizer,
+    };
+    assert_eq!(
+        tokenizer.tokenize("Sooo gr8 2day!!!"),
+        ["so", "great", "today"]
+    );
+}
@@ -0,0 +1,235 @@
+//! Placeholder Normalization
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://docs.rs/regex/latest/regex/
+//! https://nlp.stanford.edu/projects/glove/preprocess-twitter.rb
+
+use std::ops::Range;
+
+use regex::Regex;
+
+use crate::tokenize::Tokenizer;
+
+/// Classes of sparse items that are replaced by a placeholder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceholderKind {
+    Url,
+    User,
+    Date,
+    Number,
+}
+
+impl PlaceholderKind {
+    /// Every kind, in the order they are matched.
+    pub const ALL: [PlaceholderKind; 4] = [
+        PlaceholderKind::Url,
+        PlaceholderKind::User,
+        PlaceholderKind::Date,
+        PlaceholderKind::Number,
+    ];
+
+    /// The placeholder put in place of an item of this kind.
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            PlaceholderKind::Url => "<url>",
+            PlaceholderKind::User => "<user>",
+            PlaceholderKind::Date => "<date>",
+            PlaceholderKind::Number => "<num>",
+        }
+    }
+
+    /// Returns the kind for a name: url, user, date or num.
+    pub fn from_name(name: &str) -> Option<PlaceholderKind> {
+        match name {
+            "url" => Some(PlaceholderKind::Url),
+            "user" => Some(PlaceholderKind::User),
+            "date" => Some(PlaceholderKind::Date),
+            "num" => Some(PlaceholderKind::Number),
+            _ => None,
+        }
+    }
+
+    // Regex group name and pattern for the kind.
+    // Dates are 2023-05-12, 12/05/23, May 12th, 2023 or 12 May 2023, with month names in full or abbreviated.
+    fn pattern(&self) -> (&'static str, &'static str) {
+        match self {
+            PlaceholderKind::Url => ("url", r"(?:https?://|www\.)[^\s]+"),
+            PlaceholderKind::User => ("user", r"@\w+"),
+            PlaceholderKind::Date => (
+                "date",
+                r"\b(?:\d{4}-\d{1,2}-\d{1,2}|\d{1,2}[/.-]\d{1,2}[/.-]\d{2,4}|(?:jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sep(?:t(?:ember)?)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?)\.? \d{1,2}(?:st|nd|rd|th)?(?:,? \d{4})?|\d{1,2}(?:st|nd|rd|th)? (?:of )?(?:jan(?:uary)?|feb(?:ruary)?|mar(?:ch)?|apr(?:il)?|may|june?|july?|aug(?:ust)?|sep(?:t(?:ember)?)?|oct(?:ober)?|nov(?:ember)?|dec(?:ember)?)\.?(?:,? \d{4})?)\b",
+            ),
+            PlaceholderKind::Number => (
+                "num",
+                r"[-+]?\$?\b\d+(?:[.,:]\d+)*(?:st|nd|rd|th|k|m|%)?\b%?",
+            ),
+        }
+    }
+}
+
+/// An item replaced by a placeholder, kept as a side channel.
+/// span is the byte range of the original text in the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacement {
+    pub kind: PlaceholderKind,
+    pub original: String,
+    pub span: Range<usize>,
+}
+
+/// Replaces URLs, @users, dates and numbers by class placeholders, before a line is tokenized.
+#[derive(Debug, Clone)]
+pub struct PlaceholderNormalizer {
+    kinds: Vec<PlaceholderKind>,
+    regex: Regex,
+}
+
+impl Default for PlaceholderNormalizer {
+    fn default() -> Self {
+        PlaceholderNormalizer::new(&PlaceholderKind::ALL)
+    }
+}
+
+impl PlaceholderNormalizer {
+    /// Creates a normalizer replacing only the passed kinds.
+    /// Where kinds overlap, URLs win over users, dates and numbers, and dates over numbers.
+    pub fn new(kinds: &[PlaceholderKind]) -> PlaceholderNormalizer {
+        let kinds: Vec<PlaceholderKind> = PlaceholderKind::ALL
+            .into_iter()
+            .filter(|kind| kinds.contains(kind))
+            .collect();
+        let groups: Vec<String> = kinds
+            .iter()
+            .map(|kind| {
+                let (name, pattern) = kind.pattern();
+                format!("(?P<{}>{})", name, pattern)
+            })
+            .collect();
+        // A pattern that never matches when no kind is enabled.
+        let pattern = if groups.is_empty() {
+            r"[^\s\S]".to_string()
+        } else {
+            format!("(?i){}", groups.join("|"))
+        };
+        PlaceholderNormalizer {
+            kinds,
+            regex: Regex::new(&pattern).unwrap(),
+        }
+    }
+
+    /// Finds the items to replace in a line, in order.
+    pub fn find(&self, line: &str) -> Vec<Replacement> {
+        self.regex
+            .captures_iter(line)
+            .map(|caps| {
+                let kind = *self
+                    .kinds
+                    .iter()
+                    .find(|kind| caps.name(kind.pattern().0).is_some())
+                    .unwrap();
+                let found = caps.get(0).unwrap();
+                Replacement {
+                    kind,
+                    original: found.as_str().to_string(),
+                    span: found.range(),
+                }
+            })
+            .collect()
+    }
+
+    /// Accepts a line and returns it with items replaced by their placeholders.
+    pub fn normalize(&self, line: &str) -> String {
+        self.normalize_with_originals(line).0
+    }
+
+    /// Same as normalize(), also returning the replaced items in order, so the originals can be recovered.
+    pub fn normalize_with_originals(&self, line: &str) -> (String, Vec<Replacement>) {
+        let replacements = self.find(line);
+        let mut out = String::new();
+        let mut start = 0;
+        for replacement in &replacements {
+            out.push_str(&line[start..replacement.span.start]);
+            out.push_str(replacement.kind.placeholder());
+            start = replacement.span.end;
+        }
+        out.push_str(&line[start..]);
+        (out, replacements)
+    }
+}
+
+/// Tokenizer that replaces items with placeholders, keeping each placeholder as a single token
+/// and passing the text between them to another Tokenizer.
+pub struct PlaceholderTokenizer {
+    pub inner: Box<dyn Tokenizer>,
+    pub normalizer: PlaceholderNormalizer,
+}
+
+impl Tokenizer for PlaceholderTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut start = 0;
+        for replacement in self.normalizer.find(line) {
+            out.extend(self.inner.tokenize(&line[start..replacement.span.start]));
+            out.push(replacement.kind.placeholder().to_string());
+            start = replacement.span.end;
+        }
+        out.extend(self.inner.tokenize(&line[start..]));
+        out
+    }
+}
+
+/// Checks each placeholder kind, the side channel of originals, and the placeholder tokenizer.
+#[test]
+fn test_placeholder_normalizer() {
+    let line = "@waymo rode 3 times on May 5th, 2023 http://t.co/tvjopemtb for $4.50";
+    let normalizer = PlaceholderNormalizer::default();
+    let (normalized, originals) = normalizer.normalize_with_originals(line);
+    assert_eq!(
+        normalized,
+        "<user> rode <num> times on <date> <url> for <num>"
+    );
+    let kept: Vec<&str> = originals.iter().map(|r| r.original.as_str()).collect();
+    assert_eq!(
+        kept,
+        [
+            "@waymo",
+            "3",
+            "May 5th, 2023",
+            "http://t.co/tvjopemtb",
+            "$4.50"
+        ]
+    );
+    assert_eq!(&line[originals[0].span.clone()], "@waymo");
+
+    assert_eq!(
+        normalizer.normalize("due 2023-05-12 or 12/05/23"),
+        "due <date> or <date>"
+    );
+    assert_eq!(
+        normalizer.normalize("Sept. 3rd or 4 December 2023"),
+        "<date> or <date>"
+    );
+    // Words that only start like a month are not dates.
+    assert_eq!(
+        normalizer.normalize("stock 5 Marathon 26 decided 3 junk 2 11 mayors 12 decoys"),
+        "stock <num> Marathon <num> decided <num> junk <num> <num> mayors <num> decoys"
+    );
+    let numbers_only = PlaceholderNormalizer::new(&[PlaceholderKind::Number]);
+    assert_eq!(
+        numbers_only.normalize("@a1 has 20% on 12/05"),
+        "@a1 has <num> on <num>/<num>"
+    );
+
+    let tokenizer = PlaceholderTokenizer {
+        inner: Box::new(crate::tokenize::tokenize_line_alphas_lowercase),
+        normalizer,
+    };
+    assert_eq!(
+        tokenizer.tokenize("Loved it http://t.co/x 10 times"),
+        ["loved", "it", "<url>", "<num>", "times"]
+    );
+}
@@ -0,0 +1,421 @@
+//! Corpus Statistics
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://en.wikipedia.org/wiki/Zipf%27s_law
+//! https://en.wikipedia.org/wiki/Heaps%27_law
+//! https://en.wikipedia.org/wiki/Hapax_legomenon
+//! https://en.wikipedia.org/wiki/Simple_linear_regression
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    ffi::OsStr,
+};
+
+use csv::Reader;
+
+use crate::bpe_history::quote;
+use crate::tokenize::Tokenizer;
+
+/// Percentiles of the row lengths reported by LengthSummary.
+pub const LENGTH_PERCENTILES: [u8; 5] = [10, 25, 75, 90, 99];
+
+/// Summary of the number of tokens in each row of a corpus.
+/// percentiles holds the length at each of LENGTH_PERCENTILES, interpolated between rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LengthSummary {
+    pub mean: f64,
+    pub median: f64,
+    pub min: usize,
+    pub max: usize,
+    pub percentiles: Vec<(u8, f64)>,
+}
+
+/// A power law y = coefficient * x^exponent, fitted by least squares on the logs of x and y.
+/// r_squared is the share of the variance of log y that the fit explains.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CurveFit {
+    pub coefficient: f64,
+    pub exponent: f64,
+    pub r_squared: f64,
+}
+
+/// Statistics of a labeled corpus, from CorpusStats::from_rows() or CorpusStats::from_csv().
+/// classes and the top token lists are ordered most frequent first, ties by name.
+/// hapax_share is the share of the vocabulary seen exactly once.
+/// zipf fits token frequency against frequency rank, and heaps fits vocabulary size against tokens read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorpusStats {
+    pub rows: usize,
+    pub classes: Vec<(String, usize)>,
+    pub tokens: usize,
+    pub lengths: LengthSummary,
+    pub vocabulary: usize,
+    pub type_token_ratio: f64,
+    pub hapax_share: f64,
+    pub top_tokens: Vec<(String, usize)>,
+    pub top_tokens_by_class: Vec<(String, Vec<(String, usize)>)>,
+    pub zipf: CurveFit,
+    pub heaps: CurveFit,
+}
+
+impl CorpusStats {
+    /// Accepts the label and tokens of each row, and the number of top tokens to keep.
+    pub fn from_rows(rows: &[(String, Vec<String>)], top_n: usize) -> CorpusStats {
+        let mut class_rows: HashMap<&str, usize> = HashMap::new();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        let mut class_counts: HashMap<&str, HashMap<&str, usize>> = HashMap::new();
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut growth: Vec<(f64, f64)> = Vec::new();
+        let mut lengths: Vec<usize> = Vec::new();
+        let mut tokens = 0;
+        for (label, line) in rows {
+            *class_rows.entry(label).or_insert(0) += 1;
+            let in_class = class_counts.entry(label).or_default();
+            for token in line {
+                *counts.entry(token).or_insert(0) += 1;
+                *in_class.entry(token).or_insert(0) += 1;
+                seen.insert(token);
+            }
+            tokens += line.len();
+            lengths.push(line.len());
+            if tokens > 0 {
+                growth.push((tokens as f64, seen.len() as f64));
+            }
+        }
+
+        let mut top_tokens = ranked(&counts);
+        let frequencies: Vec<(f64, f64)> = top_tokens
+            .iter()
+            .enumerate()
+            .map(|(rank, (_, count))| ((rank + 1) as f64, *count as f64))
+            .collect();
+        let hapaxes = counts.values().filter(|count| **count == 1).count();
+        let mut top_tokens_by_class: Vec<(String, Vec<(String, usize)>)> = class_counts
+            .iter()
+            .map(|(label, counts)| {
+                let mut top = ranked(counts);
+                top.truncate(top_n);
+                (label.to_string(), top)
+            })
+            .collect();
+        top_tokens_by_class.sort_by(|a, b| a.0.cmp(&b.0));
+        top_tokens.truncate(top_n);
+
+        CorpusStats {
+            rows: rows.len(),
+            classes: ranked(&class_rows),
+            tokens,
+            lengths: summarize_lengths(lengths),
+            vocabulary: counts.len(),
+            type_token_ratio: ratio(counts.len(), tokens),
+            hapax_share: ratio(hapaxes, counts.len()),
+            top_tokens,
+            top_tokens_by_class,
+            zipf: fit_power_law(&frequencies),
+            heaps: fit_power_law(&growth),
+        }
+    }
+
+    /// Accepts a path to a CSV file with the label in the first column and the text in the second,
+    /// the Tokenizer to break down the text, and the number of top tokens to keep.
+    /// Returns the statistics of the corpus, or any resultant errors.
+    pub fn from_csv(
+        fpath: &OsStr,
+        tokenizer: &dyn Tokenizer,
+        top_n: usize,
+    ) -> Result<CorpusStats, Box<dyn Error>> {
+        let mut rows = Vec::new();
+        let mut reader = Reader::from_path(fpath)?;
+        for result in reader.byte_records() {
+            let record = result?;
+            let label = String::from_utf8_lossy(record.get(0).unwrap_or_default()).into_owned();
+            let tokens =
+                tokenizer.tokenize(&String::from_utf8_lossy(record.get(1).unwrap_or_default()));
+            rows.push((label, tokens));
+        }
+        Ok(CorpusStats::from_rows(&rows, top_n))
+    }
+
+    /// Formats the statistics as a plain text table, with the top tokens overall and per class side by side.
+    pub fn to_table(&self) -> String {
+        let classes: Vec<String> = self
+            .classes
+            .iter()
+            .map(|(label, rows)| {
+                format!(
+                    "{} {} ({:.1}%)",
+                    label,
+                    rows,
+                    100.0 * ratio(*rows, self.rows)
+                )
+            })
+            .collect();
+        let percentiles: Vec<String> = self
+            .lengths
+            .percentiles
+            .iter()
+            .map(|(p, length)| format!("p{} {:.1}", p, length))
+            .collect();
+        let rows = [
+            ("Rows", self.rows.to_string()),
+            ("Classes", classes.join(", ")),
+            ("Tokens", self.tokens.to_string()),
+            (
+                "Tokens per row",
+                format!(
+                    "mean {:.2}, median {:.1}, min {}, max {}",
+                    self.lengths.mean, self.lengths.median, self.lengths.min, self.lengths.max
+                ),
+            ),
+            ("Percentiles", percentiles.join(", ")),
+            ("Vocabulary", self.vocabulary.to_string()),
+            ("Type-token ratio", format!("{:.4}", self.type_token_ratio)),
+            (
+                "Hapax legomena",
+                format!("{:.1}% of vocabulary", 100.0 * self.hapax_share),
+            ),
+            (
+                "Zipf fit",
+                format!(
+                    "frequency = {:.2} * rank^{:.4} (R^2 {:.4})",
+                    self.zipf.coefficient, self.zipf.exponent, self.zipf.r_squared
+                ),
+            ),
+            (
+                "Heaps fit",
+                format!(
+                    "vocabulary = {:.2} * tokens^{:.4} (R^2 {:.4})",
+                    self.heaps.coefficient, self.heaps.exponent, self.heaps.r_squared
+                ),
+            ),
+        ];
+        let mut out = String::new();
+        for (name, value) in rows {
+            out.push_str(&format!("{:<18}{}\n", name, value));
+        }
+
+        // One column of top tokens overall, then one per class.
+        let mut columns: Vec<(String, &Vec<(String, usize)>)> =
+            vec![("overall".to_string(), &self.top_tokens)];
+        for (label, top) in &self.top_tokens_by_class {
+            columns.push((format!("class {}", label), top));
+        }
+        let cells: Vec<Vec<String>> = columns
+            .iter()
+            .map(|(heading, top)| {
+                let mut cells = vec![heading.clone()];
+                cells.extend(
+                    top.iter()
+                        .map(|(token, count)| format!("{} {}", token, count)),
+                );
+                cells
+            })
+            .collect();
+        let depth = cells.iter().map(Vec::len).max().unwrap_or(0);
+        out.push('\n');
+        for line in 0..depth {
+            let rank = if line == 0 {
+                "Rank".to_string()
+            } else {
+                line.to_string()
+            };
+            let mut text = format!("{:<6}", rank);
+            for column in &cells {
+                let width = column
+                    .iter()
+                    .map(|cell| cell.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                let cell = column.get(line).map_or("", String::as_str);
+                text.push_str(&format!("{:<width$}  ", cell, width = width));
+            }
+            out.push_str(text.trim_end());
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Formats the statistics as a JSON object.
+    pub fn to_json(&self) -> String {
+        let counts = |list: &[(String, usize)]| {
+            let entries: Vec<String> = list
+                .iter()
+                .map(|(name, count)| format!("[{}, {}]", quote(name), count))
+                .collect();
+            format!("[{}]", entries.join(", "))
+        };
+        let fit = |fit: &CurveFit| {
+            format!(
+                "{{\"coefficient\": {}, \"exponent\": {}, \"r_squared\": {}}}",
+                fit.coefficient, fit.exponent, fit.r_squared
+            )
+        };
+        let percentiles: Vec<String> = self
+            .lengths
+            .percentiles
+            .iter()
+            .map(|(p, length)| format!("\"p{}\": {}", p, length))
+            .collect();
+        let by_class: Vec<String> = self
+            .top_tokens_by_class
+            .iter()
+            .map(|(label, top)| format!("{}: {}", quote(label), counts(top)))
+            .collect();
+        let fields = [
+            format!("\"rows\": {}", self.rows),
+            format!("\"classes\": {}", counts(&self.classes)),
+            format!("\"tokens\": {}", self.tokens),
+            format!(
+                "\"tokens_per_row\": {{\"mean\": {}, \"median\": {}, \"min\": {}, \"max\": {}, {}}}",
+                self.lengths.mean,
+                self.lengths.median,
+                self.lengths.min,
+                self.lengths.max,
+                percentiles.join(", ")
+            ),
+            format!("\"vocabulary\": {}", self.vocabulary),
+            format!("\"type_token_ratio\": {}", self.type_token_ratio),
+            format!("\"hapax_share\": {}", self.hapax_share),
+            format!("\"top_tokens\": {}", counts(&self.top_tokens)),
+            format!("\"top_tokens_by_class\": {{{}}}", by_class.join(", ")),
+            format!("\"zipf\": {}", fit(&self.zipf)),
+            format!("\"heaps\": {}", fit(&self.heaps)),
+        ];
+        format!("{{\n  {}\n}}\n", fields.join(",\n  "))
+    }
+}
+
+// Share of part in whole, or 0 for an empty whole.
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        0.0
+    } else {
+        part as f64 / whole as f64
+    }
+}
+
+// Entries of a count table, most frequent first, ties by name.
+fn ranked(counts: &HashMap<&str, usize>) -> Vec<(String, usize)> {
+    let mut out: Vec<(String, usize)> = counts
+        .iter()
+        .map(|(name, count)| (name.to_string(), *count))
+        .collect();
+    out.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    out
+}
+
+// Mean, median, extremes and LENGTH_PERCENTILES of the row lengths.
+fn summarize_lengths(mut lengths: Vec<usize>) -> LengthSummary {
+    lengths.sort_unstable();
+    LengthSummary {
+        mean: ratio(lengths.iter().sum(), lengths.len()),
+        median: percentile(&lengths, 50),
+        min: lengths.first().copied().unwrap_or(0),
+        max: lengths.last().copied().unwrap_or(0),
+        percentiles: LENGTH_PERCENTILES
+            .iter()
+            .map(|p| (*p, percentile(&lengths, *p)))
+            .collect(),
+    }
+}
+
+// The p-th percentile of sorted values, interpolating linearly between the two closest ranks.
+fn percentile(sorted: &[usize], p: u8) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let position = f64::from(p) / 100.0 * (sorted.len() - 1) as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    let fraction = position - below as f64;
+    sorted[below] as f64 + fraction * (sorted[above] as f64 - sorted[below] as f64)
+}
+
+// Least squares fit of log y = log coefficient + exponent * log x.
+// Fewer than two distinct x values give an empty fit.
+fn fit_power_law(points: &[(f64, f64)]) -> CurveFit {
+    let logs: Vec<(f64, f64)> = points
+        .iter()
+        .filter(|(x, y)| *x > 0.0 && *y > 0.0)
+        .map(|(x, y)| (x.ln(), y.ln()))
+        .collect();
+    let n = logs.len() as f64;
+    let mean_x = logs.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = logs.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let sxx: f64 = logs.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    let sxy: f64 = logs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let syy: f64 = logs.iter().map(|(_, y)| (y - mean_y).powi(2)).sum();
+    if logs.len() < 2 || sxx == 0.0 {
+        return CurveFit::default();
+    }
+    let exponent = sxy / sxx;
+    CurveFit {
+        coefficient: (mean_y - exponent * mean_x).exp(),
+        exponent,
+        r_squared: if syy == 0.0 {
+            1.0
+        } else {
+            sxy * sxy / (sxx * syy)
+        },
+    }
+}
+
+/// Checks the counts, length summary and curve fits on a small corpus, and both output formats.
+#[test]
+fn test_corpus_stats() {
+    let rows: Vec<(String, Vec<String>)> = [
+        ("pos", "the car is the best"),
+        ("neg", "the car crashed"),
+        ("neg", "no"),
+        ("pos", "best car"),
+    ]
+    .iter()
+    .map(|(label, line)| {
+        (
+            label.to_string(),
+            crate::tokenize::tokenize_line_alphas_lowercase(line),
+        )
+    })
+    .collect();
+    let stats = CorpusStats::from_rows(&rows, 2);
+    assert_eq!(stats.rows, 4);
+    assert_eq!(
+        stats.classes,
+        [("neg".to_string(), 2), ("pos".to_string(), 2)]
+    );
+    assert_eq!(stats.tokens, 11);
+    assert_eq!(stats.lengths.median, 2.5);
+    assert_eq!((stats.lengths.min, stats.lengths.max), (1, 5));
+    assert_eq!(stats.lengths.percentiles[1], (25, 1.75));
+    assert_eq!(stats.vocabulary, 6);
+    assert_eq!(stats.hapax_share, 0.5);
+    assert_eq!(
+        stats.top_tokens,
+        [("car".to_string(), 3), ("the".to_string(), 3)]
+    );
+    assert_eq!(stats.top_tokens_by_class[0].0, "neg");
+    assert_eq!(stats.top_tokens_by_class[1].1[0], ("best".to_string(), 2));
+
+    // An exact power law is recovered by the fit.
+    let points: Vec<(f64, f64)> = (1..=50)
+        .map(|x| (x as f64, 120.0 * (x as f64).powf(-1.1)))
+        .collect();
+    let fit = fit_power_law(&points);
+    assert!((fit.coefficient - 120.0).abs() < 1e-6);
+    assert!((fit.exponent + 1.1).abs() < 1e-9);
+    assert!((fit.r_squared - 1.0).abs() < 1e-9);
+    assert_eq!(fit_power_law(&[(1.0, 2.0)]), CurveFit::default());
+
+    let table = stats.to_table();
+    assert!(table.contains("Vocabulary        6\n"));
+    assert!(table.contains("Rank  overall  class neg  class pos"));
+    let json = stats.to_json();
+    assert!(json.contains("\"classes\": [[\"neg\", 2], [\"pos\", 2]]"));
+    assert!(json.contains("\"top_tokens_by_class\": {\"neg\": [[\"car\", 1], [\"crashed\", 1]]"));
+}
@@ -0,0 +1,443 @@
+//! Snowball Stemmers
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://snowballstem.org/algorithms/english/stemmer.html
+//! https://snowballstem.org/algorithms/german/stemmer.html
+//! https://github.com/snowballstem/snowball-data
+
+use crate::negation::map_unnegated;
+use crate::tokenize::Tokenizer;
+
+/// Languages with a stemmer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StemLanguage {
+    English,
+    German,
+}
+
+impl StemLanguage {
+    /// Returns the language for a name such as english or de, or None if there is no stemmer for it.
+    pub fn from_name(name: &str) -> Option<StemLanguage> {
+        match name.to_lowercase().as_str() {
+            "english" | "en" => Some(StemLanguage::English),
+            "german" | "de" => Some(StemLanguage::German),
+            _ => None,
+        }
+    }
+
+    /// Stems a single word.
+    pub fn stem(&self, word: &str) -> String {
+        match self {
+            StemLanguage::English => stem_english(word),
+            StemLanguage::German => stem_german(word),
+        }
+    }
+}
+
+/// Tokenizer that stems the output of another Tokenizer,
+/// so "crash", "crashes" and "crashed" become one feature.
+pub struct StemmingTokenizer {
+    pub inner: Box<dyn Tokenizer>,
+    pub language: StemLanguage,
+}
+
+impl Tokenizer for StemmingTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        self.inner
+            .tokenize(line)
+            .iter()
+            .map(|token| map_unnegated(token, |word| self.language.stem(word)))
+            .collect()
+    }
+}
+
+// Returns true if the chars end with suffix.
+fn ends_with(word: &[char], suffix: &str) -> bool {
+    let suffix: Vec<char> = suffix.chars().collect();
+    word.ends_with(&suffix)
+}
+
+// Returns the longest of the suffixes the word ends with.
+fn longest_suffix<'a>(word: &[char], suffixes: &[&'a str]) -> Option<&'a str> {
+    suffixes
+        .iter()
+        .filter(|suffix| ends_with(word, suffix))
+        .max_by_key(|suffix| suffix.chars().count())
+        .copied()
+}
+
+// Replaces the last n chars of the word.
+fn replace_end(word: &mut Vec<char>, n: usize, with: &str) {
+    word.truncate(word.len() - n);
+    word.extend(with.chars());
+}
+
+// Returns the position after the first non-vowel that follows a vowel, at or after start.
+fn region_start(word: &[char], start: usize, is_vowel: fn(char) -> bool) -> usize {
+    let mut i = start;
+    while i < word.len() && !is_vowel(word[i]) {
+        i += 1;
+    }
+    while i < word.len() && is_vowel(word[i]) {
+        i += 1;
+    }
+    (i + 1).min(word.len())
+}
+
+fn is_english_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y')
+}
+
+// Returns true if the word ends with a short syllable:
+// a non-vowel other than w, x or Y after a vowel after a non-vowel,
+// or a vowel at the start of the word followed by a non-vowel.
+fn english_short_syllable(word: &[char]) -> bool {
+    match word {
+        [.., a, b, c] if !is_english_vowel(*a) && is_english_vowel(*b) => {
+            !is_english_vowel(*c) && !matches!(c, 'w' | 'x' | 'Y')
+        }
+        [b, c] => is_english_vowel(*b) && !is_english_vowel(*c),
+        _ => false,
+    }
+}
+
+/// Porter2 (Snowball English) stemmer.
+/// Accepts a word, which is lowercased first, and returns its stem.
+pub fn stem_english(word: &str) -> String {
+    let lower = word.to_lowercase();
+    let exception = match lower.as_str() {
+        "skis" => Some("ski"),
+        "skies" => Some("sky"),
+        "dying" => Some("die"),
+        "lying" => Some("lie"),
+        "tying" => Some("tie"),
+        "idly" => Some("idl"),
+        "gently" => Some("gentl"),
+        "ugly" => Some("ugli"),
+        "early" => Some("earli"),
+        "only" => Some("onli"),
+        "singly" => Some("singl"),
+        "sky" | "news" | "howe" | "atlas" | "cosmos" | "bias" | "andes" => Some(lower.as_str()),
+        _ => None,
+    };
+    if let Some(stem) = exception {
+        return stem.to_string();
+    }
+    let mut w: Vec<char> = lower.chars().collect();
+    if w.len() < 3 {
+        return lower;
+    }
+
+    // Prelude: drop an initial apostrophe and mark y used as a consonant.
+    if w[0] == '\'' {
+        w.remove(0);
+    }
+    if w.first() == Some(&'y') {
+        w[0] = 'Y';
+    }
+    for i in 1..w.len() {
+        if w[i] == 'y' && is_english_vowel(w[i - 1]) {
+            w[i] = 'Y';
+        }
+    }
+
+    let p1 =
+        if w.starts_with(&['g', 'e', 'n', 'e', 'r']) || w.starts_with(&['a', 'r', 's', 'e', 'n']) {
+            5
+        } else if w.starts_with(&['c', 'o', 'm', 'm', 'u', 'n']) {
+            6
+        } else {
+            region_start(&w, 0, is_english_vowel)
+        };
+    let p2 = region_start(&w, p1, is_english_vowel);
+    let has_vowel = |part: &[char]| part.iter().any(|&c| is_english_vowel(c));
+
+    // Step 1a: possessives and plurals.
+    if let Some(suffix) = longest_suffix(&w, &["'", "'s", "'s'"]) {
+        replace_end(&mut w, suffix.len(), "");
+    }
+    match longest_suffix(&w, &["sses", "ied", "ies", "s", "us", "ss"]) {
+        Some("sses") => replace_end(&mut w, 4, "ss"),
+        Some("ied" | "ies") => {
+            let with = if w.len() > 4 { "i" } else { "ie" };
+            replace_end(&mut w, 3, with);
+        }
+        Some("s") if w.len() >= 2 && has_vowel(&w[..w.len() - 2]) => replace_end(&mut w, 1, ""),
+        _ => {}
+    }
+
+    let exception = w.iter().collect::<String>();
+    if !matches!(
+        exception.as_str(),
+        "inning" | "outing" | "canning" | "herring" | "earring" | "proceed" | "exceed" | "succeed"
+    ) {
+        // Step 1b: past tenses and gerunds.
+        let suffixes = ["eed", "eedly", "ed", "edly", "ing", "ingly"];
+        match longest_suffix(&w, &suffixes) {
+            Some(suffix @ ("eed" | "eedly")) if w.len() - suffix.len() >= p1 => {
+                replace_end(&mut w, suffix.len(), "ee");
+            }
+            // Outside R1, -eed is left alone rather than treated as -ed.
+            Some("eed" | "eedly") => {}
+            Some(suffix) => {
+                let stem_len = w.len() - suffix.len();
+                if has_vowel(&w[..stem_len]) {
+                    w.truncate(stem_len);
+                    if ends_with(&w, "at") || ends_with(&w, "bl") || ends_with(&w, "iz") {
+                        w.push('e');
+                    } else if let [.., a, b] = w[..] {
+                        if a == b
+                            && matches!(b, 'b' | 'd' | 'f' | 'g' | 'm' | 'n' | 'p' | 'r' | 't')
+                        {
+                            w.pop();
+                        } else if w.len() == p1 && english_short_syllable(&w) {
+                            w.push('e');
+                        }
+                    }
+                }
+            }
+            None => {}
+        }
+
+        // Step 1c: y after a consonant, not at the start of the word.
+        let n = w.len();
+        if n > 2 && matches!(w[n - 1], 'y' | 'Y') && !is_english_vowel(w[n - 2]) {
+            w[n - 1] = 'i';
+        }
+
+        // Step 2: double suffixes, in R1.
+        let step2 = [
+            ("tional", "tion"),
+            ("enci", "ence"),
+            ("anci", "ance"),
+            ("abli", "able"),
+            ("entli", "ent"),
+            ("izer", "ize"),
+            ("ization", "ize"),
+            ("ational", "ate"),
+            ("ation", "ate"),
+            ("ator", "ate"),
+            ("alism", "al"),
+            ("aliti", "al"),
+            ("alli", "al"),
+            ("fulness", "ful"),
+            ("ousli", "ous"),
+            ("ousness", "ous"),
+            ("iveness", "ive"),
+            ("iviti", "ive"),
+            ("biliti", "ble"),
+            ("bli", "ble"),
+            ("ogi", "og"),
+            ("fulli", "ful"),
+            ("lessli", "less"),
+            ("li", ""),
+        ];
+        let suffixes: Vec<&str> = step2.iter().map(|(suffix, _)| *suffix).collect();
+        if let Some(suffix) = longest_suffix(&w, &suffixes) {
+            let start = w.len() - suffix.len();
+            let with = step2.iter().find(|(s, _)| *s == suffix).unwrap().1;
+            let before = start.checked_sub(1).map(|i| w[i]);
+            if start >= p1 {
+                match suffix {
+                    "ogi" if before != Some('l') => {}
+                    "li" if !before.is_some_and(|c| "cdeghkmnrt".contains(c)) => {}
+                    _ => replace_end(&mut w, suffix.len(), with),
+                }
+            }
+        }
+
+        // Step 3: more suffixes, in R1, with -ative also in R2.
+        let step3 = [
+            ("tional", "tion"),
+            ("ational", "ate"),
+            ("alize", "al"),
+            ("icate", "ic"),
+            ("iciti", "ic"),
+            ("ical", "ic"),
+            ("ful", ""),
+            ("ness", ""),
+            ("ative", ""),
+        ];
+        let suffixes: Vec<&str> = step3.iter().map(|(suffix, _)| *suffix).collect();
+        if let Some(suffix) = longest_suffix(&w, &suffixes) {
+            let start = w.len() - suffix.len();
+            let with = step3.iter().find(|(s, _)| *s == suffix).unwrap().1;
+            if start >= p1 && (suffix != "ative" || start >= p2) {
+                replace_end(&mut w, suffix.len(), with);
+            }
+        }
+
+        // Step 4: single suffixes, in R2.
+        let step4 = [
+            "al", "ance", "ence", "er", "ic", "able", "ible", "ant", "ement", "ment", "ent", "ism",
+            "ate", "iti", "ous", "ive", "ize", "ion",
+        ];
+        if let Some(suffix) = longest_suffix(&w, &step4) {
+            let start = w.len() - suffix.len();
+            if start >= p2
+                && (suffix != "ion"
+                    || start
+                        .checked_sub(1)
+                        .is_some_and(|i| matches!(w[i], 's' | 't')))
+            {
+                w.truncate(start);
+            }
+        }
+
+        // Step 5: a final e, or the second l of ll, in R2.
+        let n = w.len();
+        match w.last() {
+            Some('e') if n > p2 || (n > p1 && !english_short_syllable(&w[..n - 1])) => {
+                w.pop();
+            }
+            Some('l') if n > p2 && n >= 2 && w[n - 2] == 'l' => {
+                w.pop();
+            }
+            _ => {}
+        }
+    }
+
+    w.iter().map(|&c| if c == 'Y' { 'y' } else { c }).collect()
+}
+
+fn is_german_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y' | 'ä' | 'ö' | 'ü')
+}
+
+/// Snowball German stemmer.
+/// Accepts a word, which is lowercased first, and returns its stem with umlauts removed.
+pub fn stem_german(word: &str) -> String {
+    let mut w: Vec<char> = word.to_lowercase().replace('ß', "ss").chars().collect();
+
+    // Prelude: mark u and y between vowels as consonants.
+    for i in 1..w.len().saturating_sub(1) {
+        if is_german_vowel(w[i - 1]) && is_german_vowel(w[i + 1]) {
+            match w[i] {
+                'u' => w[i] = 'U',
+                'y' => w[i] = 'Y',
+                _ => {}
+            }
+        }
+    }
+
+    let (p1, p2) = if w.len() < 3 {
+        (w.len(), w.len())
+    } else {
+        // R1 is adjusted to start after at least 3 letters, but R2 is still found from where R1 was.
+        let p1 = region_start(&w, 0, is_german_vowel);
+        (p1.max(3), region_start(&w, p1, is_german_vowel))
+    };
+    let s_ending = |c: char| "bdfghklmnrt".contains(c);
+
+    // Step 1: inflectional endings, in R1.
+    if let Some(suffix) = longest_suffix(&w, &["em", "ern", "er", "e", "en", "es", "s"]) {
+        let start = w.len() - suffix.chars().count();
+        if start >= p1 {
+            if suffix != "s" {
+                w.truncate(start);
+                if matches!(suffix, "e" | "en" | "es") && ends_with(&w, "niss") {
+                    w.pop();
+                }
+            } else if start >= 1 && s_ending(w[start - 1]) {
+                w.truncate(start);
+            }
+        }
+    }
+
+    // Step 2: more inflectional endings, in R1.
+    if let Some(suffix) = longest_suffix(&w, &["en", "er", "est", "st"]) {
+        let start = w.len() - suffix.len();
+        if start >= p1 {
+            // -st needs a valid ending before it, itself after at least 3 letters.
+            if suffix != "st" || start >= 4 && s_ending(w[start - 1]) && w[start - 1] != 'r' {
+                w.truncate(start);
+            }
+        }
+    }
+
+    // Step 3: derivational endings, in R2.
+    let suffixes = ["end", "ung", "ig", "ik", "isch", "lich", "heit", "keit"];
+    if let Some(suffix) = longest_suffix(&w, &suffixes) {
+        let start = w.len() - suffix.len();
+        let after_e = start >= 1 && w[start - 1] == 'e';
+        if start >= p2 {
+            match suffix {
+                "end" | "ung" => {
+                    w.truncate(start);
+                    let n = w.len();
+                    if ends_with(&w, "ig") && n - 2 >= p2 && !(n >= 3 && w[n - 3] == 'e') {
+                        w.truncate(n - 2);
+                    }
+                }
+                "ig" | "ik" | "isch" if !after_e => w.truncate(start),
+                "lich" | "heit" => {
+                    w.truncate(start);
+                    if (ends_with(&w, "er") || ends_with(&w, "en")) && w.len() - 2 >= p1 {
+                        w.truncate(w.len() - 2);
+                    }
+                }
+                "keit" => {
+                    w.truncate(start);
+                    if let Some(inner) = longest_suffix(&w, &["lich", "ig"]) {
+                        if w.len() - inner.len() >= p2 {
+                            w.truncate(w.len() - inner.len());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    w.iter()
+        .map(|&c| match c {
+            'Y' => 'y',
+            'U' | 'ü' => 'u',
+            'ä' => 'a',
+            'ö' => 'o',
+            c => c,
+        })
+        .collect()
+}
+
+// Checks a stemmer against a Snowball test file of word and expected stem pairs.
+#[cfg(test)]
+fn check_snowball_list(file: &str, stem: fn(&str) -> String) {
+    let mut filepath = std::env::current_dir().unwrap();
+    filepath.push(file);
+    let text = std::fs::read_to_string(filepath).unwrap();
+    let mut checked = 0;
+    for line in text.lines() {
+        let (word, expected) = line.split_once(' ').unwrap();
+        assert_eq!(stem(word), expected, "Stem of {}", word);
+        checked += 1;
+    }
+    assert!(checked > 20_000);
+}
+
+/// Validates the English stemmer against the Snowball vocabulary and output lists.
+/// Expects snowball-english.txt to be in the root directory of the crate.
+#[test]
+fn test_stem_english() {
+    check_snowball_list("snowball-english.txt", stem_english);
+    let tokenizer = StemmingTokenizer {
+        inner: Box::new(crate::tokenize::tokenize_line_alphas_lowercase),
+        language: StemLanguage::English,
+    };
+    assert_eq!(
+        tokenizer.tokenize("Crash, crashes, CRASHED!"),
+        ["crash", "crash", "crash"]
+    );
+}
+
+/// Validates the German stemmer against the Snowball vocabulary and output lists.
+/// Expects snowball-german.txt to be in the root directory of the crate.
+#[test]
+fn test_stem_german() {
+    check_snowball_list("snowball-german.txt", stem_german);
+}
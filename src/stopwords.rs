@@ -0,0 +1,416 @@
+//! Stopword Filtering
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://snowballstem.org/algorithms/
+//! https://www.nltk.org/book/ch02.html
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    error::Error,
+    ffi::OsStr,
+    fs,
+};
+
+use crate::negation::unnegated;
+use crate::tokenize::{lowercase, Tokenizer};
+
+// Negators, such as "not", "pas", "nicht", "no", "non", "não" and "niet", are left out of every list,
+// as they flip the sentiment of what follows.
+const ENGLISH: &[&str] = &[
+    "a",
+    "about",
+    "above",
+    "after",
+    "again",
+    "against",
+    "all",
+    "am",
+    "an",
+    "and",
+    "any",
+    "are",
+    "as",
+    "at",
+    "be",
+    "because",
+    "been",
+    "before",
+    "being",
+    "below",
+    "between",
+    "both",
+    "but",
+    "by",
+    "can",
+    "could",
+    "did",
+    "do",
+    "does",
+    "doing",
+    "down",
+    "during",
+    "each",
+    "few",
+    "for",
+    "from",
+    "further",
+    "had",
+    "has",
+    "have",
+    "having",
+    "he",
+    "her",
+    "here",
+    "hers",
+    "herself",
+    "him",
+    "himself",
+    "his",
+    "how",
+    "i",
+    "if",
+    "in",
+    "into",
+    "is",
+    "it",
+    "its",
+    "itself",
+    "just",
+    "me",
+    "more",
+    "most",
+    "my",
+    "myself",
+    "now",
+    "of",
+    "off",
+    "on",
+    "once",
+    "only",
+    "or",
+    "other",
+    "our",
+    "ours",
+    "ourselves",
+    "out",
+    "over",
+    "own",
+    "same",
+    "she",
+    "should",
+    "so",
+    "some",
+    "such",
+    "than",
+    "that",
+    "the",
+    "their",
+    "theirs",
+    "them",
+    "themselves",
+    "then",
+    "there",
+    "these",
+    "they",
+    "this",
+    "those",
+    "through",
+    "to",
+    "too",
+    "under",
+    "until",
+    "up",
+    "very",
+    "was",
+    "we",
+    "were",
+    "what",
+    "when",
+    "where",
+    "which",
+    "while",
+    "who",
+    "whom",
+    "why",
+    "will",
+    "with",
+    "would",
+    "you",
+    "your",
+    "yours",
+    "yourself",
+    "yourselves",
+];
+
+const FRENCH: &[&str] = &[
+    "à", "au", "aux", "avec", "ce", "ces", "dans", "de", "des", "du", "elle", "en", "et", "eux",
+    "il", "ils", "je", "la", "le", "les", "leur", "lui", "ma", "mais", "me", "même", "mes", "moi",
+    "mon", "nos", "notre", "nous", "on", "ou", "par", "pour", "qu", "que", "qui", "sa", "se",
+    "ses", "son", "sur", "ta", "te", "tes", "toi", "ton", "tu", "un", "une", "vos", "votre",
+    "vous", "c", "d", "j", "l", "m", "s", "t", "y", "été", "est", "sont", "était", "être", "avoir",
+    "ai", "as", "a", "ont",
+];
+
+const GERMAN: &[&str] = &[
+    "aber", "alle", "als", "also", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "bist",
+    "da", "dann", "das", "dass", "dem", "den", "der", "des", "die", "dies", "diese", "dieser",
+    "du", "durch", "ein", "eine", "einem", "einen", "einer", "er", "es", "für", "hat", "hatte",
+    "ich", "ihr", "ihre", "im", "in", "ist", "ja", "kann", "mein", "mich", "mir", "mit", "nach",
+    "noch", "nur", "ob", "oder", "sich", "sie", "sind", "so", "über", "um", "und", "uns", "unter",
+    "vom", "von", "vor", "war", "waren", "was", "weil", "wenn", "wer", "wie", "wir", "wird", "zu",
+    "zum", "zur",
+];
+
+const SPANISH: &[&str] = &[
+    "a", "al", "algo", "ante", "con", "como", "de", "del", "donde", "el", "él", "ella", "ellos",
+    "en", "entre", "era", "es", "esa", "ese", "esta", "está", "este", "esto", "fue", "ha", "hay",
+    "la", "las", "le", "les", "lo", "los", "me", "mi", "muy", "más", "nos", "o", "para", "pero",
+    "por", "porque", "que", "qué", "se", "sea", "ser", "si", "sí", "sin", "sobre", "son", "su",
+    "sus", "también", "te", "tu", "tú", "un", "una", "uno", "y", "ya", "yo",
+];
+
+const ITALIAN: &[&str] = &[
+    "a", "ad", "al", "alla", "anche", "che", "chi", "ci", "come", "con", "da", "dal", "del",
+    "della", "di", "e", "è", "ed", "gli", "ha", "hanno", "i", "il", "in", "io", "la", "le", "lei",
+    "lo", "lui", "ma", "mi", "ne", "nel", "nella", "noi", "o", "per", "più", "quella", "quello",
+    "questa", "questo", "se", "si", "sono", "su", "sua", "suo", "ti", "tu", "un", "una", "uno",
+    "voi",
+];
+
+const PORTUGUESE: &[&str] = &[
+    "a", "ao", "aos", "as", "com", "como", "da", "das", "de", "do", "dos", "e", "é", "ela", "ele",
+    "eles", "em", "entre", "era", "essa", "esse", "esta", "este", "eu", "foi", "há", "isso", "já",
+    "mais", "mas", "me", "meu", "muito", "na", "nas", "no", "nos", "o", "os", "ou", "para", "pela",
+    "pelo", "por", "quando", "que", "se", "sem", "seu", "sua", "são", "também", "te", "um", "uma",
+    "você",
+];
+
+const DUTCH: &[&str] = &[
+    "aan", "al", "als", "bij", "dan", "dat", "de", "der", "die", "dit", "door", "een", "en", "er",
+    "had", "heb", "heeft", "het", "hij", "hoe", "hun", "ik", "in", "is", "je", "kan", "maar", "me",
+    "met", "mij", "naar", "nog", "nu", "of", "om", "ons", "ook", "op", "over", "te", "tot", "uit",
+    "van", "voor", "was", "wat", "we", "wel", "werd", "wij", "zal", "ze", "zich", "zij", "zijn",
+    "zo",
+];
+
+/// Languages with a built-in stopword list.
+pub const STOPWORD_LANGUAGES: [&str; 7] = [
+    "english",
+    "french",
+    "german",
+    "spanish",
+    "italian",
+    "portuguese",
+    "dutch",
+];
+
+/// Set of tokens to drop from a token stream.
+/// Tokens are compared lowercased, so the filter works after case-keeping tokenizers too.
+#[derive(Debug, Clone, Default)]
+pub struct StopwordFilter {
+    words: HashSet<String>,
+}
+
+impl StopwordFilter {
+    /// Creates an empty filter.
+    pub fn new() -> StopwordFilter {
+        StopwordFilter::default()
+    }
+
+    /// Creates a filter holding the passed words.
+    pub fn from_words<I, S>(words: I) -> StopwordFilter
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut filter = StopwordFilter::new();
+        filter.extend(words);
+        filter
+    }
+
+    /// Creates a filter from the built-in list of a language, one of STOPWORD_LANGUAGES.
+    /// Returns None for a language without a list.
+    pub fn for_language(language: &str) -> Option<StopwordFilter> {
+        let words = match language.to_lowercase().as_str() {
+            "english" | "en" => ENGLISH,
+            "french" | "fr" => FRENCH,
+            "german" | "de" => GERMAN,
+            "spanish" | "es" => SPANISH,
+            "italian" | "it" => ITALIAN,
+            "portuguese" | "pt" => PORTUGUESE,
+            "dutch" | "nl" => DUTCH,
+            _ => return None,
+        };
+        Some(StopwordFilter::from_words(words))
+    }
+
+    /// Accepts a path to a text file with one stopword per line.
+    /// Blank lines and lines starting with # are skipped.
+    /// Returns the filter, or any resultant errors.
+    pub fn from_file(fpath: &OsStr) -> Result<StopwordFilter, Box<dyn Error>> {
+        let text = fs::read_to_string(fpath)?;
+        Ok(StopwordFilter::from_words(
+            text.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#')),
+        ))
+    }
+
+    /// Derives a filter from a tokenized corpus, holding its k most frequent tokens.
+    /// Ties are broken alphabetically so the result does not depend on HashMap order.
+    pub fn from_top_k(corpus: &[Vec<String>], k: usize) -> StopwordFilter {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for token in corpus.iter().flatten() {
+            *counts.entry(token.to_lowercase()).or_insert(0) += 1;
+        }
+        let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        StopwordFilter::from_words(counts.into_iter().take(k).map(|(token, _)| token))
+    }
+
+    /// Derives a filter from a tokenized corpus, holding every token
+    /// found in more than max_df of the documents, given as a fraction from 0 to 1.
+    pub fn from_document_frequency(corpus: &[Vec<String>], max_df: f64) -> StopwordFilter {
+        let mut document_counts: HashMap<String, usize> = HashMap::new();
+        for document in corpus {
+            let unique: HashSet<String> = document.iter().map(|t| t.to_lowercase()).collect();
+            for token in unique {
+                *document_counts.entry(token).or_insert(0) += 1;
+            }
+        }
+        let documents = corpus.len() as f64;
+        StopwordFilter::from_words(
+            document_counts
+                .into_iter()
+                .filter(|(_, count)| *count as f64 / documents > max_df)
+                .map(|(token, _)| token),
+        )
+    }
+
+    /// Adds words to the filter.
+    pub fn extend<I, S>(&mut self, words: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.words
+            .extend(words.into_iter().map(|w| w.as_ref().to_lowercase()));
+    }
+
+    /// Removes words from the filter, so they are kept in the token stream.
+    pub fn remove<I, S>(&mut self, words: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for word in words {
+            self.words.remove(&word.as_ref().to_lowercase());
+        }
+    }
+
+    /// Iterates over the stopwords, in no particular order.
+    pub fn words(&self) -> impl Iterator<Item = &String> {
+        self.words.iter()
+    }
+
+    /// Returns true if token is a stopword, ignoring any negation prefix.
+    pub fn is_stopword(&self, token: &str) -> bool {
+        self.words.contains(lowercase(unnegated(token)).as_ref())
+    }
+
+    /// Returns the tokens that are not stopwords.
+    pub fn filter(&self, tokens: Vec<String>) -> Vec<String> {
+        tokens
+            .into_iter()
+            .filter(|token| !self.is_stopword(token))
+            .collect()
+    }
+
+    /// Number of stopwords in the filter.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    /// Returns true if the filter holds no stopwords.
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
+
+/// Tokenizer that drops stopwords from the output of another Tokenizer.
+pub struct StopwordTokenizer {
+    pub inner: Box<dyn Tokenizer>,
+    pub stopwords: StopwordFilter,
+}
+
+impl Tokenizer for StopwordTokenizer {
+    fn tokenize(&self, line: &str) -> Vec<String> {
+        self.stopwords.filter(self.inner.tokenize(line))
+    }
+
+    fn tokens<'a>(&'a self, line: &'a str) -> Box<dyn Iterator<Item = Cow<'a, str>> + 'a> {
+        Box::new(
+            self.inner
+                .tokens(line)
+                .filter(|token| !self.stopwords.is_stopword(token)),
+        )
+    }
+}
+
+/// Checks the built-in lists, corpus-derived filters and the filtering tokenizer.
+#[test]
+fn test_stopword_filter() {
+    for language in STOPWORD_LANGUAGES {
+        assert!(!StopwordFilter::for_language(language).unwrap().is_empty());
+    }
+    assert!(StopwordFilter::for_language("klingon").is_none());
+
+    let english = StopwordFilter::for_language("english").unwrap();
+    assert!(english.is_stopword("The"));
+    assert!(!english.is_stopword("car"));
+    for language in STOPWORD_LANGUAGES {
+        let negators: &[&str] = match language {
+            "english" => &crate::negation::NEGATORS,
+            "french" => &[
+                "ne", "n", "pas", "jamais", "rien", "personne", "aucun", "aucune", "ni",
+            ],
+            "german" => &[
+                "nicht", "kein", "keine", "keinen", "nie", "niemals", "nichts", "weder",
+            ],
+            "spanish" => &[
+                "no", "ni", "nunca", "jamás", "nada", "nadie", "ninguno", "ninguna",
+            ],
+            "italian" => &["non", "né", "mai", "niente", "nulla", "nessuno", "nessuna"],
+            "portuguese" => &["não", "nem", "nunca", "jamais", "nada", "ninguém", "nenhum"],
+            "dutch" => &["niet", "geen", "nooit", "niets", "niemand", "noch"],
+            _ => panic!("no negators for {}", language),
+        };
+        let filter = StopwordFilter::for_language(language).unwrap();
+        for negator in negators {
+            assert!(!filter.is_stopword(negator), "{} {}", language, negator);
+        }
+    }
+
+    let corpus: Vec<Vec<String>> = ["the car is red", "the bus", "a red car"]
+        .iter()
+        .map(|line| line.split(' ').map(|t| t.to_string()).collect())
+        .collect();
+    let top = StopwordFilter::from_top_k(&corpus, 2);
+    assert_eq!(top.len(), 2);
+    assert!(top.is_stopword("car") && top.is_stopword("red"));
+    let df = StopwordFilter::from_document_frequency(&corpus, 0.5);
+    assert_eq!(df.len(), 3);
+    assert!(df.is_stopword("the") && !df.is_stopword("bus"));
+
+    let tokenizer = StopwordTokenizer {
+        inner: Box::new(crate::tokenize::tokenize_line_alphas),
+        stopwords: english,
+    };
+    assert_eq!(tokenizer.tokenize("This is NOT the car."), ["NOT", "car"]);
+}
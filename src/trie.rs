@@ -0,0 +1,266 @@
+//! Vocabulary Trie
+//! Tools for Natural Language Processing in Rust
+//!
+//! Authors: Sawyer Norquist, Genevieve LaLonde
+//! Programming in Rust 2023
+//! Professor Dr. Bart Massey 2023
+//! Resources:
+//! https://doc.rust-lang.org/reference/
+//! https://en.wikipedia.org/wiki/Trie
+//! https://huggingface.co/learn/nlp-course/chapter6/6?fw=pt
+
+use std::collections::HashMap;
+
+/// Prefix WordPiece puts in front of tokens that continue a word.
+pub const WORDPIECE_PREFIX: &str = "##";
+
+#[derive(Debug, Clone, Default)]
+struct TrieNode {
+    children: HashMap<char, usize>,
+    terminal: bool,
+}
+
+/// Character trie over the entries of a vocab.
+/// Built once, it is read-only and can be shared between threads and tokenizers.
+/// Lookups cost the length of the matched text, rather than the size of the vocab.
+#[derive(Debug, Clone)]
+pub struct VocabTrie {
+    nodes: Vec<TrieNode>,
+    len: usize,
+}
+
+impl Default for VocabTrie {
+    fn default() -> Self {
+        VocabTrie::new()
+    }
+}
+
+impl VocabTrie {
+    /// Creates an empty trie.
+    pub fn new() -> VocabTrie {
+        VocabTrie {
+            nodes: vec![TrieNode::default()],
+            len: 0,
+        }
+    }
+
+    /// Builds a trie holding every token of the iterator.
+    pub fn from_tokens<I, S>(tokens: I) -> VocabTrie
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut trie = VocabTrie::new();
+        for token in tokens {
+            trie.insert(token.as_ref());
+        }
+        trie
+    }
+
+    /// Adds a token to the trie.  Empty tokens are ignored.
+    pub fn insert(&mut self, token: &str) {
+        if token.is_empty() {
+            return;
+        }
+        let mut node = 0;
+        for c in token.chars() {
+            node = match self.nodes[node].children.get(&c) {
+                Some(&next) => next,
+                None => {
+                    self.nodes.push(TrieNode::default());
+                    let next = self.nodes.len() - 1;
+                    self.nodes[node].children.insert(c, next);
+                    next
+                }
+            };
+        }
+        if !self.nodes[node].terminal {
+            self.nodes[node].terminal = true;
+            self.len += 1;
+        }
+    }
+
+    /// Number of distinct tokens in the trie.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the trie holds no tokens.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Follow text down from the root, returning the node it ends on.
+    fn walk(&self, text: &str) -> Option<usize> {
+        let mut node = 0;
+        for c in text.chars() {
+            node = *self.nodes[node].children.get(&c)?;
+        }
+        Some(node)
+    }
+
+    /// Returns true if token is in the vocab.
+    pub fn contains(&self, token: &str) -> bool {
+        self.walk(token)
+            .is_some_and(|node| self.nodes[node].terminal)
+    }
+
+    /// Returns the length in bytes of the longest vocab token that text starts with.
+    /// If prefix is given, only tokens starting with prefix are considered, and the prefix is not counted.
+    fn longest_match_after(&self, prefix: &str, text: &str) -> Option<usize> {
+        let mut node = self.walk(prefix)?;
+        let mut longest = None;
+        for (i, c) in text.char_indices() {
+            match self.nodes[node].children.get(&c) {
+                Some(&next) => node = next,
+                None => break,
+            }
+            if self.nodes[node].terminal {
+                longest = Some(i + c.len_utf8());
+            }
+        }
+        longest
+    }
+
+    /// Returns the longest vocab token that text starts with, if any.
+    pub fn longest_prefix<'a>(&self, text: &'a str) -> Option<&'a str> {
+        self.longest_match_after("", text).map(|end| &text[..end])
+    }
+
+    /// Dictionary segmentation: splits text greedily into the longest vocab tokens.
+    /// Characters matching no token are replaced by unknown.
+    pub fn segment_longest_match(&self, text: &str, unknown: &str) -> Vec<String> {
+        let mut segments = Vec::new();
+        let mut rest = text;
+        while let Some(c) = rest.chars().next() {
+            match self.longest_prefix(rest) {
+                Some(token) => {
+                    segments.push(token.to_string());
+                    rest = &rest[token.len()..];
+                }
+                None => {
+                    segments.push(unknown.to_string());
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+        segments
+    }
+
+    /// WordPiece segmentation of a single word.
+    /// Pieces after the first are looked up with WORDPIECE_PREFIX in front, and returned with it.
+    /// If any part of the word cannot be matched, the whole word becomes unknown.
+    pub fn segment_wordpiece(&self, word: &str, unknown: &str) -> Vec<String> {
+        let mut segments = Vec::new();
+        let mut rest = word;
+        while !rest.is_empty() {
+            let prefix = if segments.is_empty() {
+                ""
+            } else {
+                WORDPIECE_PREFIX
+            };
+            match self.longest_match_after(prefix, rest) {
+                Some(end) => {
+                    segments.push(format!("{}{}", prefix, &rest[..end]));
+                    rest = &rest[end..];
+                }
+                None => return vec![unknown.to_string()],
+            }
+        }
+        segments
+    }
+}
+
+/// Checks membership, longest prefix, dictionary and WordPiece segmentation on a small vocab.
+#[test]
+fn test_vocab_trie() {
+    let trie = VocabTrie::from_tokens(["un", "unhappy", "happ", "##happ", "##y", "y", "é"]);
+
+    assert_eq!(trie.len(), 7);
+    assert!(trie.contains("unhappy"));
+    assert!(!trie.contains("unh"));
+    assert_eq!(trie.longest_prefix("unhappyness"), Some("unhappy"));
+    assert_eq!(trie.longest_prefix("xyz"), None);
+
+    assert_eq!(
+        trie.segment_longest_match("unhappéy!", "?"),
+        ["un", "happ", "é", "y", "?"]
+    );
+    assert_eq!(trie.segment_wordpiece("unhappy", "[UNK]"), ["unhappy"]);
+    assert_eq!(trie.segment_wordpiece("unhapp", "[UNK]"), ["un", "##happ"]);
+    assert_eq!(trie.segment_wordpiece("unhappx", "[UNK]"), ["[UNK]"]);
+}
+
+/// Compares trie segmentation with the previous sorted-vocab scan on the progressive tweet corpus.
+/// The vocab is the trained BPE vocab plus every distinct corpus word, to give it a realistic size.
+/// Run with: cargo test --release -- --ignored --nocapture bench_trie_segmentation
+#[test]
+#[ignore]
+fn bench_trie_segmentation() {
+    use crate::bpe::{parse_csv_to_tokens, parse_txt_to_tokens};
+    use std::{env, time::Instant};
+
+    let mut corpus_path = env::current_dir().unwrap();
+    let mut vocab_path = env::current_dir().unwrap();
+    corpus_path.push("progressive-tweet-sentiment-train.csv");
+    vocab_path.push("BPE-VOCAB-progressive-tweet-sentiment-train.txt");
+    let lines = parse_csv_to_tokens(corpus_path.as_os_str()).unwrap();
+    let vocab_lines = parse_txt_to_tokens(vocab_path.as_os_str()).unwrap();
+
+    let mut vocab: Vec<String> = vocab_lines
+        .iter()
+        .map(|entry| {
+            if entry.len() == 2 {
+                format!("{}</w>", entry[0])
+            } else {
+                entry[0].clone()
+            }
+        })
+        .collect();
+    vocab.extend(lines.iter().flatten().map(|word| format!("{}</w>", word)));
+    vocab.sort();
+    vocab.dedup();
+    let words: Vec<String> = lines
+        .iter()
+        .flatten()
+        .map(|word| format!("{}</w>", word))
+        .collect();
+
+    let start = Instant::now();
+    let mut sorted = vocab.clone();
+    radsort::sort_by_key(&mut sorted, |s| s.len());
+    let mut scanned = Vec::new();
+    for word in &words {
+        let mut rest = word.as_str();
+        while let Some(c) = rest.chars().next() {
+            match sorted.iter().rev().find(|t| rest.starts_with(t.as_str())) {
+                Some(token) => {
+                    scanned.push(token.clone());
+                    rest = &rest[token.len()..];
+                }
+                None => {
+                    scanned.push("?".to_string());
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+    }
+    let scan_time = start.elapsed();
+
+    let start = Instant::now();
+    let trie = VocabTrie::from_tokens(&vocab);
+    let mut walked = Vec::new();
+    for word in &words {
+        walked.extend(trie.segment_longest_match(word, "?"));
+    }
+    let trie_time = start.elapsed();
+
+    assert_eq!(scanned, walked);
+    println!(
+        "{} words, {} vocab entries: sorted scan {:?}, trie {:?}",
+        words.len(),
+        vocab.len(),
+        scan_time,
+        trie_time
+    );
+}